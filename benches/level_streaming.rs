@@ -0,0 +1,124 @@
+//! Sweeps a camera-sized [`ChunkLoader`] across a large generated level, streaming it the way
+//! the game does, and checks that the tile entities alive stay bounded by the view, not the
+//! level, and that every chunk left behind is despawned along with its children.
+//!
+//! Run with `cargo bench --bench level_streaming`.
+
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::ecs::query::WorldQuery;
+use bevy::prelude::*;
+use dr_fingers::level::{self, ChunkLoader, Level, LevelGrid, LevelStreaming, Side, Tile};
+
+const LEVEL_WIDTH: usize = 2000;
+const LEVEL_HEIGHT: usize = 150;
+const HALF_VIEW: Vec2 = bevy::math::const_vec2!([400.0, 300.0]);
+
+/// A solid floor with rows of platforms, ladders and vents, so most chunks have something in
+/// them, and some tiles have children of their own.
+fn generate_level() -> Level {
+    let mut level = Level::new(LEVEL_WIDTH, LEVEL_HEIGHT);
+
+    for x in 0..LEVEL_WIDTH {
+        level.set(x, LEVEL_HEIGHT - 1, Tile::Floor(Side::Top));
+
+        for y in (4..LEVEL_HEIGHT - 1).step_by(5) {
+            if (x + y * 3) % 11 < 6 {
+                level.set(x, y, Tile::Floor(Side::Middle));
+            } else if (x + y) % 13 == 0 {
+                level.set(x, y, Tile::Ladder);
+            } else if (x + y) % 17 == 0 {
+                level.set(x, y, Tile::Vent);
+            }
+        }
+    }
+
+    level
+}
+
+fn count<Q: WorldQuery>(world: &mut World) -> usize {
+    world.query::<Q>().iter(world).count()
+}
+
+fn main() {
+    let level = generate_level();
+    let grid = LevelGrid::default();
+    let streaming = LevelStreaming::default();
+    let (min, max) = grid.level_bounds(&level);
+
+    // every cell of every chunk touching the view plus margin, even when the view straddles
+    // chunk borders, each with at most one child
+    let chunk_world = streaming.chunk_size as f32 * grid.tile_size;
+    let chunks_across = ((HALF_VIEW + streaming.margin) * 2.0 / chunk_world).ceil() + 1.0;
+    let bound = (chunks_across.x * chunks_across.y) as usize * streaming.chunk_size.pow(2) * 2;
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins).add_plugin(AssetPlugin);
+    level::add_streaming(&mut app, level);
+
+    let camera = app
+        .world
+        .spawn()
+        .insert_bundle(TransformBundle::default())
+        .insert(OrthographicProjection {
+            left: -HALF_VIEW.x,
+            right: HALF_VIEW.x,
+            bottom: -HALF_VIEW.y,
+            top: HALF_VIEW.y,
+            ..Default::default()
+        })
+        .insert(ChunkLoader)
+        .id();
+
+    let (mut peak_tiles, mut peak_entities, mut frames) = (0, 0, 0);
+    let mut streaming_time = Duration::ZERO;
+
+    // zig-zag down the level in camera-sized strips, one step per frame
+    let mut center = min + HALF_VIEW;
+    let mut direction = 1.0;
+    while center.y < max.y {
+        app.world.get_mut::<Transform>(camera).unwrap().translation = center.extend(999.0);
+
+        let start = Instant::now();
+        app.update();
+        streaming_time += start.elapsed();
+        frames += 1;
+
+        // everything spawned is a tile or one of their children, besides the camera
+        let tiles = count::<&Tile>(&mut app.world);
+        let entities = app.world.entities().len() as usize - 1;
+        peak_tiles = peak_tiles.max(tiles);
+        peak_entities = peak_entities.max(entities);
+        assert!(
+            entities <= bound,
+            "{entities} entities alive at {center}, expected at most {bound}"
+        );
+
+        center.x += direction * 8.0;
+        if center.x > max.x - HALF_VIEW.x || center.x < min.x + HALF_VIEW.x {
+            direction = -direction;
+            center.y += HALF_VIEW.y * 2.0;
+        }
+    }
+
+    // back to the start: only the chunks there are left, however much was streamed since
+    app.world.get_mut::<Transform>(camera).unwrap().translation = (min + HALF_VIEW).extend(999.0);
+    app.update();
+    let tiles = count::<&Tile>(&mut app.world);
+    let updrafts = count::<&level::Updraft>(&mut app.world);
+    let entities = app.world.entities().len() as usize - 1;
+    assert_eq!(
+        entities,
+        tiles + updrafts,
+        "every entity alive should be a tile in view or its updraft"
+    );
+
+    println!("level: {LEVEL_WIDTH}x{LEVEL_HEIGHT} cells");
+    println!(
+        "frames: {frames}, {:?} streaming per frame",
+        streaming_time / frames
+    );
+    println!("peak alive: {peak_tiles} tiles, {peak_entities} entities (bound {bound})");
+    println!("back at the start: {tiles} tiles, {updrafts} updrafts");
+}
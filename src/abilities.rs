@@ -0,0 +1,329 @@
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::actions::Actions;
+use crate::animation::Facing;
+use crate::kinematic::KinematicSystem;
+use crate::level::Updraft;
+use crate::player::{JumpState, MovementMode, MovementStats, Player, PlayerSystem};
+use crate::timestep::GameTime;
+use crate::GameState;
+
+pub struct AbilitiesPlugin;
+
+/// This plugin handles the special abilities a player can have, on top of the
+/// basic movement in the `PlayerPlugin`
+impl Plugin for AbilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AbilityEvent>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(
+                    wall_jump
+                        .label(AbilitySystem::WallJump)
+                        .after(PlayerSystem::Movement)
+                        .before(PlayerSystem::Animation)
+                        .before(KinematicSystem::Move),
+                )
+                .with_system(
+                    double_jump
+                        .after(PlayerSystem::Movement)
+                        .after(AbilitySystem::WallJump)
+                        .before(PlayerSystem::Animation)
+                        .before(KinematicSystem::Move),
+                )
+                .with_system(
+                    glide
+                        .label(AbilitySystem::Glide)
+                        .after(PlayerSystem::Movement)
+                        .before(PlayerSystem::Animation)
+                        .before(KinematicSystem::Move),
+                )
+                .with_system(
+                    dash.after(PlayerSystem::Movement)
+                        .after(AbilitySystem::WallJump)
+                        .after(AbilitySystem::Glide)
+                        .before(PlayerSystem::Animation)
+                        .before(KinematicSystem::Move),
+                ),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+enum AbilitySystem {
+    /// A wall jump takes priority over a double jump while touching a wall.
+    WallJump,
+    /// A dash overrides gliding, so it isn't held to the glide's speeds.
+    Glide,
+}
+
+/// Sent whenever an ability is used, e.g. to play a sound for it.
+pub enum AbilityEvent {
+    DoubleJump,
+    WallJump,
+    Dash,
+}
+
+/// Lets the player jump again while in the air.
+#[derive(Component)]
+pub struct DoubleJump {
+    /// How many jumps can be made before landing again.
+    pub extra_jumps: u32,
+    /// Upward velocity of each extra jump.
+    pub velocity: f32,
+    remaining: u32,
+}
+
+impl DoubleJump {
+    pub fn new(extra_jumps: u32) -> Self {
+        Self {
+            extra_jumps,
+            velocity: 180.0,
+            remaining: extra_jumps,
+        }
+    }
+}
+
+/// Lets the player hold jump while falling to drift down slowly, and ride updrafts.
+#[derive(Component)]
+pub struct Glide {
+    /// Fastest the player can fall while gliding.
+    pub max_fall_speed: f32,
+    /// Extra horizontal acceleration per second while gliding.
+    pub air_control: f32,
+    gliding: bool,
+}
+
+impl Default for Glide {
+    fn default() -> Self {
+        Self {
+            max_fall_speed: 40.0,
+            air_control: 600.0,
+            gliding: false,
+        }
+    }
+}
+
+impl Glide {
+    pub fn is_gliding(&self) -> bool {
+        self.gliding
+    }
+}
+
+/// Lets the player slide slowly down walls while pushing into them, and jump off them.
+#[derive(Component)]
+pub struct WallJump {
+    /// Fastest the player can slide down a wall.
+    pub max_slide_speed: f32,
+    /// Velocity of a jump off a wall to the right; mirrored for walls to the left.
+    pub kick: Vec2,
+    sliding: bool,
+}
+
+impl Default for WallJump {
+    fn default() -> Self {
+        Self {
+            max_slide_speed: 60.0,
+            kick: Vec2::new(-175.0, 200.0),
+            sliding: false,
+        }
+    }
+}
+
+impl WallJump {
+    pub fn is_sliding(&self) -> bool {
+        self.sliding
+    }
+}
+
+/// Lets the player burst forwards, on the ground or in the air, going past their top speed
+/// and ignoring gravity until the dash is over.
+#[derive(Component)]
+pub struct Dash {
+    /// Horizontal velocity during a dash.
+    pub speed: f32,
+    /// Seconds a dash lasts.
+    pub duration: f32,
+    /// Seconds after a dash is over before the next one.
+    pub cooldown: f32,
+    /// Whether a dash in the air is used up until the player lands again.
+    pub recharge_on_landing: bool,
+    /// Which way the current dash goes: -1.0 for left, 1.0 for right.
+    direction: f32,
+    time_left: f32,
+    cooldown_left: f32,
+    charged: bool,
+}
+
+impl Dash {
+    pub fn new(recharge_on_landing: bool) -> Self {
+        Self {
+            speed: 400.0,
+            duration: 0.15,
+            cooldown: 0.4,
+            recharge_on_landing,
+            direction: 1.0,
+            time_left: 0.0,
+            cooldown_left: 0.0,
+            charged: true,
+        }
+    }
+
+    pub fn is_dashing(&self) -> bool {
+        self.time_left > 0.0
+    }
+}
+
+fn double_jump(
+    mut events: EventWriter<AbilityEvent>,
+    mut query: Query<
+        (
+            &mut DoubleJump,
+            &mut JumpState,
+            &mut Velocity,
+            &MovementMode,
+        ),
+        With<Player>,
+    >,
+) {
+    for (mut double_jump, mut jump, mut velocity, mode) in query.iter_mut() {
+        if *mode != MovementMode::Normal {
+            continue;
+        }
+
+        if jump.on_floor() {
+            double_jump.remaining = double_jump.extra_jumps;
+        } else if jump.buffered.is_some() && double_jump.remaining > 0 {
+            velocity.linear.y = double_jump.velocity;
+            jump.buffered = None;
+            jump.rising = true;
+
+            double_jump.remaining -= 1;
+            events.send(AbilityEvent::DoubleJump);
+        }
+    }
+}
+
+type Glider = (
+    &'static mut Glide,
+    &'static mut Velocity,
+    &'static JumpState,
+    &'static MovementMode,
+    &'static MovementStats,
+    &'static Collisions,
+);
+
+fn glide(
+    time: Res<GameTime>,
+    actions: Res<Actions>,
+    updrafts: Query<&Updraft>,
+    mut query: Query<Glider, With<Player>>,
+) {
+    for (mut glide, mut velocity, jump, mode, stats, collisions) in query.iter_mut() {
+        let updraft = collisions
+            .entities()
+            .find_map(|entity| updrafts.get(entity).ok());
+
+        glide.gliding = *mode == MovementMode::Normal
+            && !jump.on_floor()
+            && actions.player_jump
+            && (velocity.linear.y <= 0.0 || updraft.is_some());
+
+        if !glide.gliding {
+            continue;
+        }
+
+        if let Some(updraft) = updraft {
+            velocity.linear.y = (velocity.linear.y + updraft.strength * time.delta_seconds())
+                .min(updraft.max_speed);
+        } else {
+            velocity.linear.y = velocity.linear.y.max(-glide.max_fall_speed);
+        }
+
+        if let Some(movement) = actions.player_x_movement {
+            velocity.linear.x = (velocity.linear.x
+                + movement * glide.air_control * time.delta_seconds())
+            .clamp(-stats.max_speed_x, stats.max_speed_x);
+        }
+    }
+}
+
+fn wall_jump(
+    actions: Res<Actions>,
+    mut events: EventWriter<AbilityEvent>,
+    mut query: Query<(&mut WallJump, &mut JumpState, &mut Velocity, &MovementMode), With<Player>>,
+) {
+    for (mut wall_jump, mut jump, mut velocity, mode) in query.iter_mut() {
+        let wall_side = match jump.wall_side {
+            Some(side) if *mode == MovementMode::Normal && !jump.on_floor() => side,
+            _ => {
+                wall_jump.sliding = false;
+                continue;
+            }
+        };
+
+        if jump.buffered.is_some() {
+            velocity.linear = (wall_jump.kick * Vec2::new(wall_side, 1.0)).extend(0.0);
+            jump.buffered = None;
+            jump.rising = true;
+            wall_jump.sliding = false;
+            events.send(AbilityEvent::WallJump);
+            continue;
+        }
+
+        wall_jump.sliding = actions
+            .player_x_movement
+            .is_some_and(|movement| movement.signum() == wall_side)
+            && velocity.linear.y <= 0.0;
+
+        if wall_jump.sliding {
+            velocity.linear.y = velocity.linear.y.max(-wall_jump.max_slide_speed);
+        }
+    }
+}
+
+type Dasher = (
+    &'static mut Dash,
+    &'static mut Velocity,
+    &'static mut Acceleration,
+    &'static JumpState,
+    &'static MovementMode,
+    &'static Facing,
+);
+
+fn dash(
+    time: Res<GameTime>,
+    actions: Res<Actions>,
+    gravity: Res<Gravity>,
+    mut events: EventWriter<AbilityEvent>,
+    mut query: Query<Dasher, With<Player>>,
+) {
+    let delta = time.delta_seconds();
+
+    for (mut dash, mut velocity, mut acceleration, jump, mode, facing) in query.iter_mut() {
+        dash.cooldown_left = (dash.cooldown_left - delta).max(0.0);
+        if jump.on_floor() {
+            dash.charged = true;
+        }
+
+        if *mode != MovementMode::Normal {
+            dash.time_left = 0.0;
+            continue;
+        }
+
+        if actions.player_dash && !dash.is_dashing() && dash.cooldown_left <= 0.0 && dash.charged {
+            dash.direction = actions.player_x_movement.map_or(facing.sign(), f32::signum);
+            dash.time_left = dash.duration;
+            dash.cooldown_left = dash.duration + dash.cooldown;
+            dash.charged = !dash.recharge_on_landing;
+            events.send(AbilityEvent::Dash);
+        }
+
+        if dash.is_dashing() {
+            dash.time_left -= delta;
+            // overrides the running speed clamp, and whatever else moved the player this frame
+            velocity.linear = Vec3::X * dash.direction * dash.speed;
+            acceleration.linear = -gravity.vector();
+        }
+    }
+}
@@ -1,109 +1,809 @@
+use std::collections::{BTreeMap, HashSet};
+use std::hash::Hash;
+use std::{fmt, fs, io};
+
+use bevy::input::InputSystem;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::GameState;
 
 pub struct ActionsPlugin;
 
-// This plugin listens for keyboard input and converts the input into Actions
+// This plugin listens for keyboard, mouse, gamepad and touch input and converts the input into Actions
 // Actions can then be used as a resource in other systems to act on the player input.
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Actions>().add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(set_movement_actions),
-        );
+        let input_map = InputMap::load().unwrap_or_else(|error| {
+            warn!("Using the default controls, as {CONTROLS_PATH} could not be loaded: {error}");
+            InputMap::default()
+        });
+
+        app.init_resource::<Actions>()
+            .init_resource::<ActionMapper>()
+            .init_resource::<ActiveGamepad>()
+            .init_resource::<TouchedControls>()
+            .init_resource::<ActionsSource>()
+            .init_resource::<ControlInput>()
+            .insert_resource(input_map)
+            .add_system(connect_gamepads)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                gather_control_input
+                    .label(ActionsSystem::Gather)
+                    .after(InputSystem),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(set_movement_actions.label(ActionsSystem::Read)),
+            );
     }
 }
 
-#[derive(Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum ActionsSystem {
+    /// Reads the devices into the [`ControlInput`] every frame, whether the game ticks or not.
+    /// Anything feeding them, like the touch controls, should run before this.
+    Gather,
+    /// Sets this tick's [`Actions`]. Anything acting on them should run after this.
+    Read,
+}
+
+/// Where this tick's [`Actions`] come from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ActionsSource {
+    #[default]
+    Devices,
+    /// Another system labelled [`ActionsSystem::Read`] sets them, like a replay.
+    Injected,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Actions {
     pub player_x_movement: Option<f32>,
+    pub player_y_movement: Option<f32>,
+    /// Whether jump is held down.
     pub player_jump: bool,
+    /// Whether jump was pressed this tick.
+    pub player_jump_started: bool,
+    /// Whether the player asked to swap to the next character this tick.
+    pub player_swap: bool,
+    /// Whether dash was pressed this tick.
+    pub player_dash: bool,
 }
 
-fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<KeyCode>>) {
-    if GameControl::Jump.just_released(&keyboard_input)
-        || GameControl::Jump.pressed(&keyboard_input)
-    {
-        actions.player_jump = true;
-    } else {
-        actions.player_jump = false;
-    }
-
-    if GameControl::Left.pressed(&keyboard_input)
-        || GameControl::Left.pressed(&keyboard_input)
-        || GameControl::Right.just_released(&keyboard_input)
-        || GameControl::Right.pressed(&keyboard_input)
-    {
-        let player_movement;
-        if GameControl::Right.just_released(&keyboard_input)
-            || GameControl::Left.just_released(&keyboard_input)
-        {
-            if GameControl::Right.pressed(&keyboard_input) {
-                player_movement = 1.0;
-            } else if GameControl::Left.pressed(&keyboard_input) {
-                player_movement = -1.0;
-            } else {
-                player_movement = 0.0;
+/// The gamepad the player is using, out of all the connected ones.
+#[derive(Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+/// Uses the first gamepad to be connected, until it is disconnected.
+fn connect_gamepads(
+    mut events: EventReader<GamepadEvent>,
+    gamepads: Res<Gamepads>,
+    mut active: ResMut<ActiveGamepad>,
+) {
+    for GamepadEvent(gamepad, event) in events.iter() {
+        match event {
+            GamepadEventType::Connected => {
+                info!("Gamepad {} connected", gamepad.0);
+                active.0.get_or_insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                info!("Gamepad {} disconnected", gamepad.0);
+                if active.0 == Some(*gamepad) {
+                    active.0 = gamepads.iter().find(|&other| other != gamepad).copied();
+                }
             }
-        } else if GameControl::Right.just_pressed(&keyboard_input) {
-            player_movement = 1.0;
-        } else if GameControl::Left.just_pressed(&keyboard_input) {
-            player_movement = -1.0;
-        } else {
-            player_movement = actions.player_x_movement.unwrap_or(0.0);
+            _ => {}
         }
+    }
+}
+
+/// Presses add up until the next tick takes them, as frames and ticks don't always match up
+/// once the timestep is fixed: a press on a frame without a tick would be lost otherwise.
+#[allow(clippy::too_many_arguments)]
+fn gather_control_input(
+    mut control_input: ResMut<ControlInput>,
+    state: Res<State<GameState>>,
+    input_map: Res<InputMap>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepad: Res<ActiveGamepad>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    touched: Res<TouchedControls>,
+) {
+    // presses in the menus shouldn't carry over into the game
+    if *state.current() != GameState::Playing {
+        *control_input = ControlInput::default();
+        return;
+    }
+
+    let input = RawInput {
+        keys: &keyboard_input,
+        mouse: &mouse_input,
+        gamepad: gamepad.0,
+        gamepad_buttons: &gamepad_buttons,
+        gamepad_axes: &gamepad_axes,
+        touch: &touched,
+    };
+    let mut next = input_map.read(&input);
+    next.pressed.extend(control_input.pressed.drain());
+    *control_input = next;
+}
+
+fn set_movement_actions(
+    mut actions: ResMut<Actions>,
+    mut mapper: ResMut<ActionMapper>,
+    mut control_input: ResMut<ControlInput>,
+    source: Res<ActionsSource>,
+) {
+    if *source == ActionsSource::Devices {
+        *actions = mapper.map(&control_input);
+    }
+    control_input.pressed.clear();
+}
+
+/// The controls held and pressed since the last tick, whatever they are bound to.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ControlInput {
+    pub held: HashSet<GameControl>,
+    /// Controls pressed since the last tick. They are usually held too, unless let go since.
+    pub pressed: HashSet<GameControl>,
+    /// Where the stick is pushed on each axis, outside its dead zone.
+    pub stick_x: Option<f32>,
+    pub stick_y: Option<f32>,
+}
+
+/// Turns each tick's [`ControlInput`] into [`Actions`]. Of two opposite directions held
+/// together, the last one pressed wins, until it is let go and the other one takes over.
+#[derive(Debug, Default, Clone)]
+pub struct ActionMapper {
+    x: DirectionAxis,
+    y: DirectionAxis,
+}
+
+impl ActionMapper {
+    pub fn map(&mut self, input: &ControlInput) -> Actions {
+        let held = |control| input.held.contains(&control);
+        let pressed = |control| input.pressed.contains(&control);
+
+        let x = self
+            .x
+            .update(held(GameControl::Left), held(GameControl::Right));
+        let y = self
+            .y
+            .update(held(GameControl::Down), held(GameControl::Up));
 
-        if player_movement != 0.0 {
-            actions.player_x_movement = Some(player_movement);
+        Actions {
+            // buttons and keys win over the stick
+            player_x_movement: x.or(input.stick_x),
+            player_y_movement: y.or(input.stick_y),
+            player_jump: held(GameControl::Jump),
+            player_jump_started: pressed(GameControl::Jump),
+            player_swap: pressed(GameControl::Swap),
+            player_dash: pressed(GameControl::Dash),
         }
-    } else {
-        actions.player_x_movement = None;
     }
 }
 
-enum GameControl {
+/// Two opposite directions, and which of them wins while both are held.
+#[derive(Debug, Default, Clone, Copy)]
+struct DirectionAxis {
+    negative: bool,
+    positive: bool,
+    direction: Option<f32>,
+}
+
+impl DirectionAxis {
+    fn update(&mut self, negative: bool, positive: bool) -> Option<f32> {
+        let newly_negative = negative && !self.negative;
+        let newly_positive = positive && !self.positive;
+
+        self.direction = match (negative, positive) {
+            (false, false) => None,
+            (true, false) => Some(-1.0),
+            (false, true) => Some(1.0),
+            (true, true) => match (newly_negative, newly_positive) {
+                (true, false) => Some(-1.0),
+                (false, true) => Some(1.0),
+                // pressed together they cancel out, until one is let go
+                (true, true) => None,
+                (false, false) => self.direction,
+            },
+        };
+        self.negative = negative;
+        self.positive = positive;
+
+        self.direction
+    }
+}
+
+/// Something the player can do, bound to keys or buttons by the [`InputMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GameControl {
     Jump,
     Left,
     Right,
+    Up,
+    Down,
+    Swap,
+    Dash,
 }
 
 impl GameControl {
-    fn just_released(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
+    pub const ALL: [Self; 7] = [
+        Self::Jump,
+        Self::Left,
+        Self::Right,
+        Self::Up,
+        Self::Down,
+        Self::Swap,
+        Self::Dash,
+    ];
+
+    /// How the control is shown in the settings.
+    pub fn name(self) -> &'static str {
         match self {
-            GameControl::Jump => keyboard_input.just_released(KeyCode::Space),
-            GameControl::Left => {
-                keyboard_input.just_released(KeyCode::A)
-                    || keyboard_input.just_released(KeyCode::Left)
-            }
-            GameControl::Right => {
-                keyboard_input.just_released(KeyCode::D)
-                    || keyboard_input.just_released(KeyCode::Right)
-            }
+            Self::Jump => "Jump",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Swap => "Swap character",
+            Self::Dash => "Dash",
         }
     }
+}
+
+/// A key or button that can be bound to a [`GameControl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button on whichever gamepad is the [`ActiveGamepad`].
+    Gamepad(GamepadButtonType),
+}
 
-    fn pressed(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
+impl fmt::Display for InputBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            GameControl::Jump => keyboard_input.pressed(KeyCode::Space),
-            GameControl::Left => {
-                keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left)
-            }
-            GameControl::Right => {
-                keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right)
-            }
+            Self::Key(key) => write!(f, "{key:?}"),
+            Self::Mouse(button) => write!(f, "Mouse {button:?}"),
+            Self::Gamepad(button) => write!(f, "Gamepad {button:?}"),
+        }
+    }
+}
+
+/// The state of every key, button and stick this frame.
+pub struct RawInput<'a> {
+    pub keys: &'a Input<KeyCode>,
+    pub mouse: &'a Input<MouseButton>,
+    pub gamepad: Option<Gamepad>,
+    pub gamepad_buttons: &'a Input<GamepadButton>,
+    pub gamepad_axes: &'a Axis<GamepadAxis>,
+    pub touch: &'a TouchedControls,
+}
+
+/// How far a stick has to be pushed before it moves the player at all.
+pub const STICK_DEAD_ZONE: f32 = 0.25;
+
+impl RawInput<'_> {
+    /// Where an axis of the active gamepad's sticks is pushed, from -1.0 to 1.0, or `None`
+    /// inside the dead zone. Movement starts from zero at the edge of the dead zone.
+    pub fn stick(&self, axis: GamepadAxisType) -> Option<f32> {
+        let value = self.gamepad_axes.get(GamepadAxis(self.gamepad?, axis))?;
+
+        (value.abs() > STICK_DEAD_ZONE).then(|| {
+            let scaled = (value.abs() - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE);
+            scaled.min(1.0).copysign(value)
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Transition {
+    Pressed,
+    JustPressed,
+}
+
+impl Transition {
+    fn of<T: Copy + Eq + Hash>(self, input: &Input<T>, button: T) -> bool {
+        match self {
+            Self::Pressed => input.pressed(button),
+            Self::JustPressed => input.just_pressed(button),
         }
     }
+}
+
+/// Controls held down on the on-screen touch controls, which aren't bound through the
+/// [`InputMap`] as each of their buttons stands for one control already.
+#[derive(Debug, Default)]
+pub struct TouchedControls {
+    held: HashSet<GameControl>,
+    started: HashSet<GameControl>,
+}
 
-    fn just_pressed(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
+impl TouchedControls {
+    /// Moves on to the next frame, with `held` being touched now.
+    pub fn update(&mut self, held: HashSet<GameControl>) {
+        self.started = held.difference(&self.held).copied().collect();
+        self.held = held;
+    }
+
+    fn is(&self, control: GameControl, transition: Transition) -> bool {
+        match transition {
+            Transition::Pressed => self.held.contains(&control),
+            Transition::JustPressed => self.started.contains(&control),
+        }
+    }
+}
+
+impl InputBinding {
+    fn is(self, transition: Transition, input: &RawInput) -> bool {
         match self {
-            GameControl::Jump => keyboard_input.just_pressed(KeyCode::Space),
-            GameControl::Left => {
-                keyboard_input.just_pressed(KeyCode::A)
-                    || keyboard_input.just_pressed(KeyCode::Left)
-            }
-            GameControl::Right => {
-                keyboard_input.just_pressed(KeyCode::D)
-                    || keyboard_input.just_pressed(KeyCode::Right)
+            Self::Key(key) => transition.of(input.keys, key),
+            Self::Mouse(button) => transition.of(input.mouse, button),
+            Self::Gamepad(button) => input.gamepad.is_some_and(|gamepad| {
+                transition.of(input.gamepad_buttons, GamepadButton(gamepad, button))
+            }),
+        }
+    }
+}
+
+/// A binding refused by [`InputMap::bind`], as it already triggers another control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingClash {
+    pub binding: InputBinding,
+    pub other: GameControl,
+}
+
+impl fmt::Display for BindingClash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is already bound to {}",
+            self.binding,
+            self.other.name()
+        )
+    }
+}
+
+/// Where the [`InputMap`] is kept between runs.
+pub const CONTROLS_PATH: &str = "controls.ron";
+
+/// Which keys and buttons trigger each [`GameControl`]. Any of them will do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    bindings: BTreeMap<GameControl, Vec<InputBinding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use GamepadButtonType::*;
+        use InputBinding::{Gamepad, Key};
+
+        let bindings = GameControl::ALL
+            .into_iter()
+            .map(|control| {
+                let keys = match control {
+                    GameControl::Jump => vec![Key(KeyCode::Space), Gamepad(South)],
+                    GameControl::Left => {
+                        vec![Key(KeyCode::A), Key(KeyCode::Left), Gamepad(DPadLeft)]
+                    }
+                    GameControl::Right => {
+                        vec![Key(KeyCode::D), Key(KeyCode::Right), Gamepad(DPadRight)]
+                    }
+                    GameControl::Up => vec![Key(KeyCode::W), Key(KeyCode::Up), Gamepad(DPadUp)],
+                    GameControl::Down => {
+                        vec![Key(KeyCode::S), Key(KeyCode::Down), Gamepad(DPadDown)]
+                    }
+                    GameControl::Swap => {
+                        vec![Key(KeyCode::Q), Key(KeyCode::Tab), Gamepad(North)]
+                    }
+                    GameControl::Dash => {
+                        vec![Key(KeyCode::LShift), Key(KeyCode::RShift), Gamepad(West)]
+                    }
+                };
+                (control, keys)
+            })
+            .collect();
+
+        Self { bindings }
+    }
+}
+
+impl InputMap {
+    pub fn bindings(&self, control: GameControl) -> &[InputBinding] {
+        self.bindings.get(&control).map_or(&[], Vec::as_slice)
+    }
+
+    /// The control `binding` is bound to, if any.
+    pub fn bound_to(&self, binding: InputBinding) -> Option<GameControl> {
+        self.bindings
+            .iter()
+            .find(|(_, bindings)| bindings.contains(&binding))
+            .map(|(&control, _)| control)
+    }
+
+    /// Binds `binding` to `control`, in place of `replacing` if given, or next to the other
+    /// bindings of `control` otherwise. A binding can only trigger one control, so one bound
+    /// to any other is refused.
+    pub fn bind(
+        &mut self,
+        control: GameControl,
+        binding: InputBinding,
+        replacing: Option<InputBinding>,
+    ) -> Result<(), BindingClash> {
+        match self.bound_to(binding) {
+            Some(other) if other != control => return Err(BindingClash { binding, other }),
+            _ => {}
+        }
+
+        let bindings = self.bindings.entry(control).or_default();
+        let replaced = replacing.and_then(|old| bindings.iter().position(|&b| b == old));
+        match replaced {
+            Some(i) => bindings[i] = binding,
+            None => bindings.push(binding),
+        }
+        // binding something already there again shouldn't list it twice
+        let mut seen = HashSet::new();
+        bindings.retain(|&b| seen.insert(b));
+
+        Ok(())
+    }
+
+    /// Removes one binding of `control`, leaving the others.
+    pub fn unbind(&mut self, control: GameControl, binding: InputBinding) {
+        if let Some(bindings) = self.bindings.get_mut(&control) {
+            bindings.retain(|&b| b != binding);
+        }
+    }
+
+    pub fn pressed(&self, control: GameControl, input: &RawInput) -> bool {
+        self.any(control, Transition::Pressed, input)
+    }
+
+    pub fn just_pressed(&self, control: GameControl, input: &RawInput) -> bool {
+        self.any(control, Transition::JustPressed, input)
+    }
+
+    /// Which controls are held and pressed in `input`.
+    pub fn read(&self, input: &RawInput) -> ControlInput {
+        let controls_where = |transition| {
+            GameControl::ALL
+                .into_iter()
+                .filter(|&control| self.any(control, transition, input))
+                .collect()
+        };
+
+        ControlInput {
+            held: controls_where(Transition::Pressed),
+            pressed: controls_where(Transition::JustPressed),
+            stick_x: input.stick(GamepadAxisType::LeftStickX),
+            stick_y: input.stick(GamepadAxisType::LeftStickY),
+        }
+    }
+
+    fn any(&self, control: GameControl, transition: Transition, input: &RawInput) -> bool {
+        input.touch.is(control, transition)
+            || self
+                .bindings(control)
+                .iter()
+                .any(|binding| binding.is(transition, input))
+    }
+
+    /// Reads the map from [`CONTROLS_PATH`], or the defaults if it was never saved.
+    /// Controls missing from the file keep their default bindings.
+    pub fn load() -> anyhow::Result<Self> {
+        let mut input_map = Self::default();
+
+        let contents = match fs::read_to_string(CONTROLS_PATH) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(input_map),
+            Err(error) => return Err(error.into()),
+        };
+
+        let saved: Self = ron::from_str(&contents)?;
+        input_map.bindings.extend(saved.bindings);
+        Ok(input_map)
+    }
+
+    /// Writes the map to [`CONTROLS_PATH`].
+    pub fn save(&self) -> anyhow::Result<()> {
+        let contents = ron::ser::to_string_pretty(self, Default::default())?;
+        fs::write(CONTROLS_PATH, contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use GameControl::{Down, Jump, Left, Right, Up};
+    use InputBinding::Key;
+
+    const PAD: Gamepad = Gamepad(0);
+    const OTHER_PAD: Gamepad = Gamepad(1);
+
+    /// Everything a frame of input is read from.
+    #[derive(Default)]
+    struct Devices {
+        keys: Input<KeyCode>,
+        mouse: Input<MouseButton>,
+        buttons: Input<GamepadButton>,
+        axes: Axis<GamepadAxis>,
+        touch: TouchedControls,
+        mapper: ActionMapper,
+    }
+
+    impl Devices {
+        /// Maps the current state to actions, then moves on to the next frame.
+        fn frame(&mut self, gamepad: Option<Gamepad>) -> Actions {
+            let input = RawInput {
+                keys: &self.keys,
+                mouse: &self.mouse,
+                gamepad,
+                gamepad_buttons: &self.buttons,
+                gamepad_axes: &self.axes,
+                touch: &self.touch,
+            };
+            let actions = self.mapper.map(&InputMap::default().read(&input));
+
+            self.keys.clear();
+            self.mouse.clear();
+            self.buttons.clear();
+            actions
+        }
+
+        fn stick_x(&mut self, value: f32) {
+            self.axes
+                .set(GamepadAxis(PAD, GamepadAxisType::LeftStickX), value);
+        }
+    }
+
+    fn button(button: GamepadButtonType) -> GamepadButton {
+        GamepadButton(PAD, button)
+    }
+
+    #[test]
+    fn dpad_moves_digitally() {
+        let mut devices = Devices::default();
+
+        devices.buttons.press(button(GamepadButtonType::DPadRight));
+        assert_eq!(devices.frame(Some(PAD)).player_x_movement, Some(1.0));
+
+        devices
+            .buttons
+            .release(button(GamepadButtonType::DPadRight));
+        devices.buttons.press(button(GamepadButtonType::DPadUp));
+        let actions = devices.frame(Some(PAD));
+        assert_eq!(actions.player_x_movement, None);
+        assert_eq!(actions.player_y_movement, Some(1.0));
+    }
+
+    #[test]
+    fn stick_moves_analog_outside_dead_zone() {
+        let mut devices = Devices::default();
+
+        devices.stick_x(STICK_DEAD_ZONE * 0.9);
+        assert_eq!(devices.frame(Some(PAD)).player_x_movement, None);
+
+        // halfway between the edge of the dead zone and fully pushed
+        devices.stick_x(-(STICK_DEAD_ZONE + 1.0) / 2.0);
+        let movement = devices.frame(Some(PAD)).player_x_movement.unwrap();
+        assert!((movement + 0.5).abs() < 1e-5, "got {movement}");
+
+        devices.stick_x(1.0);
+        assert_eq!(devices.frame(Some(PAD)).player_x_movement, Some(1.0));
+
+        // the d-pad wins over the stick
+        devices.buttons.press(button(GamepadButtonType::DPadLeft));
+        assert_eq!(devices.frame(Some(PAD)).player_x_movement, Some(-1.0));
+    }
+
+    #[test]
+    fn face_buttons_jump_and_dash() {
+        let mut devices = Devices::default();
+
+        devices.buttons.press(button(GamepadButtonType::South));
+        devices.buttons.press(button(GamepadButtonType::West));
+        let actions = devices.frame(Some(PAD));
+        assert!(actions.player_jump && actions.player_jump_started);
+        assert!(actions.player_dash);
+
+        // still held on the next frame, but no longer just pressed
+        let actions = devices.frame(Some(PAD));
+        assert!(actions.player_jump && !actions.player_jump_started);
+        assert!(!actions.player_dash);
+
+        devices.buttons.release(button(GamepadButtonType::South));
+        assert!(!devices.frame(Some(PAD)).player_jump);
+    }
+
+    #[test]
+    fn only_the_active_gamepad_counts() {
+        let mut devices = Devices::default();
+
+        devices
+            .buttons
+            .press(GamepadButton(OTHER_PAD, GamepadButtonType::South));
+        devices.stick_x(1.0);
+        let actions = devices.frame(Some(PAD));
+        assert!(!actions.player_jump, "button on another gamepad");
+        assert_eq!(actions.player_x_movement, Some(1.0));
+
+        // after the last gamepad is disconnected
+        assert_eq!(devices.frame(None).player_x_movement, None);
+    }
+
+    /// Maps a tick per entry, each with the controls held then, checking one axis of the
+    /// movement. Controls held on a tick but not the one before count as pressed.
+    fn assert_movement(axis: fn(&Actions) -> Option<f32>, ticks: &[(&[GameControl], Option<f32>)]) {
+        let mut mapper = ActionMapper::default();
+        let mut held_before = HashSet::new();
+
+        for (tick, &(held, movement)) in ticks.iter().enumerate() {
+            let held: HashSet<_> = held.iter().copied().collect();
+            let input = ControlInput {
+                pressed: held.difference(&held_before).copied().collect(),
+                held: held.clone(),
+                ..Default::default()
+            };
+            assert_eq!(axis(&mapper.map(&input)), movement, "tick {tick}");
+            held_before = held;
+        }
+    }
+
+    fn x(actions: &Actions) -> Option<f32> {
+        actions.player_x_movement
+    }
+
+    #[test]
+    fn last_direction_pressed_wins() {
+        assert_movement(
+            x,
+            &[
+                (&[Left], Some(-1.0)),
+                (&[Left, Right], Some(1.0)),
+                (&[Left, Right], Some(1.0)),
+                // letting go of the loser changes nothing
+                (&[Right], Some(1.0)),
+                (&[Left, Right], Some(-1.0)),
+                // letting go of the winner goes back to the other
+                (&[Right], Some(1.0)),
+                (&[], None),
+            ],
+        );
+    }
+
+    #[test]
+    fn directions_swapped_between_ticks_follow_along() {
+        assert_movement(
+            x,
+            &[
+                (&[Left], Some(-1.0)),
+                (&[Right], Some(1.0)),
+                (&[Left], Some(-1.0)),
+            ],
+        );
+    }
+
+    #[test]
+    fn directions_pressed_together_cancel_out() {
+        assert_movement(
+            x,
+            &[
+                (&[Left, Right], None),
+                (&[Left, Right], None),
+                (&[Right], Some(1.0)),
+            ],
+        );
+    }
+
+    #[test]
+    fn vertical_directions_work_the_same() {
+        assert_movement(
+            |actions| actions.player_y_movement,
+            &[
+                (&[Up], Some(1.0)),
+                (&[Up, Down], Some(-1.0)),
+                (&[Up], Some(1.0)),
+                (&[], None),
+            ],
+        );
+    }
+
+    #[test]
+    fn directions_win_over_the_stick() {
+        let mut mapper = ActionMapper::default();
+        let stick = |held: &[GameControl]| ControlInput {
+            held: held.iter().copied().collect(),
+            stick_x: Some(-0.5),
+            ..Default::default()
+        };
+
+        assert_eq!(mapper.map(&stick(&[])).player_x_movement, Some(-0.5));
+        assert_eq!(mapper.map(&stick(&[Right])).player_x_movement, Some(1.0));
+        assert_eq!(mapper.map(&stick(&[])).player_x_movement, Some(-0.5));
+    }
+
+    #[test]
+    fn jump_tapped_between_ticks_still_starts_a_jump() {
+        let mut mapper = ActionMapper::default();
+
+        let actions = mapper.map(&ControlInput {
+            pressed: HashSet::from([Jump]),
+            ..Default::default()
+        });
+        assert!(actions.player_jump_started && !actions.player_jump);
+
+        let actions = mapper.map(&ControlInput::default());
+        assert!(!actions.player_jump_started && !actions.player_jump);
+    }
+
+    #[test]
+    fn binding_replaces_only_the_one_clicked() {
+        let mut input_map = InputMap::default();
+
+        input_map
+            .bind(Left, Key(KeyCode::J), Some(Key(KeyCode::A)))
+            .unwrap();
+        assert_eq!(
+            input_map.bindings(Left),
+            [
+                Key(KeyCode::J),
+                Key(KeyCode::Left),
+                InputBinding::Gamepad(GamepadButtonType::DPadLeft)
+            ]
+        );
+    }
+
+    #[test]
+    fn bindings_are_added_and_removed_one_at_a_time() {
+        let mut input_map = InputMap::default();
+
+        input_map.bind(Jump, Key(KeyCode::K), None).unwrap();
+        input_map.bind(Jump, Key(KeyCode::K), None).unwrap();
+        assert_eq!(
+            input_map.bindings(Jump),
+            [
+                Key(KeyCode::Space),
+                InputBinding::Gamepad(GamepadButtonType::South),
+                Key(KeyCode::K)
+            ]
+        );
+
+        input_map.unbind(Jump, Key(KeyCode::Space));
+        input_map.unbind(Jump, InputBinding::Gamepad(GamepadButtonType::South));
+        assert_eq!(input_map.bindings(Jump), [Key(KeyCode::K)]);
+    }
+
+    #[test]
+    fn binding_used_by_another_control_is_refused() {
+        let mut input_map = InputMap::default();
+        let before = input_map.clone();
+
+        let clash = input_map.bind(Jump, Key(KeyCode::A), None).unwrap_err();
+        assert_eq!(clash.other, Left);
+        assert_eq!(input_map, before);
+
+        // but it can be moved around within the same control
+        input_map
+            .bind(Left, Key(KeyCode::A), Some(Key(KeyCode::Left)))
+            .unwrap();
+        assert_eq!(input_map.bound_to(Key(KeyCode::A)), Some(Left));
+        assert_eq!(input_map.bound_to(Key(KeyCode::Left)), None);
+    }
+
+    #[test]
+    fn default_bindings_dont_clash() {
+        let input_map = InputMap::default();
+
+        for control in GameControl::ALL {
+            for &binding in input_map.bindings(control) {
+                assert_eq!(input_map.bound_to(binding), Some(control), "{binding}");
             }
         }
     }
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+use crate::timestep::GameTime;
+use crate::GameState;
+
+pub struct AnimationPlugin;
+
+/// This plugin plays named clips from an [`AnimationSet`] on any sprite with an [`Animator`]
+/// Which clip plays is up to other systems, e.g. the player's state machine
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AnimationSet>()
+            .add_asset_loader(AnimationLoader)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(play_animations.label(AnimationSystem::Play))
+                    .with_system(flip_to_facing),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum AnimationSystem {
+    /// Advances every [`Animator`]; pick clips before this to show them the same frame.
+    Play,
+}
+
+/// A run of frames in a sprite sheet, loaded from an `.anim.ron` file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnimationClip {
+    /// First and last index of the clip in the texture atlas, inclusive.
+    pub frames: (usize, usize),
+    pub fps: f32,
+    #[serde(default)]
+    pub looping: bool,
+    /// Clip to continue with once a non-looping clip is done.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+impl AnimationClip {
+    fn len(&self) -> usize {
+        self.frames.1.saturating_sub(self.frames.0) + 1
+    }
+}
+
+/// All the clips for one sprite sheet, by name.
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "5b0a3c59-93e4-4a83-a4a4-2f3a4b8e71d2"]
+pub struct AnimationSet {
+    pub clips: HashMap<String, AnimationClip>,
+}
+
+/// Plays a clip from the entity's `Handle<AnimationSet>` on its `TextureAtlasSprite`.
+#[derive(Component, Debug, Clone)]
+pub struct Animator {
+    clip: String,
+    frame: usize,
+    elapsed: f32,
+    finished: bool,
+    /// Hold the current frame.
+    pub paused: bool,
+}
+
+impl Animator {
+    pub fn new(clip: &str) -> Self {
+        Self {
+            clip: clip.to_string(),
+            frame: 0,
+            elapsed: 0.0,
+            finished: false,
+            paused: false,
+        }
+    }
+
+    /// Switches to another clip from its start, unless it is already playing.
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            *self = Self::new(clip);
+        }
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+
+    /// Whether a non-looping clip has shown its last frame.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn advance(&mut self, clip: &AnimationClip, delta: f32) {
+        if self.paused || self.finished || clip.fps <= 0.0 {
+            return;
+        }
+
+        self.elapsed += delta;
+        let frame_time = clip.fps.recip();
+
+        while self.elapsed >= frame_time {
+            self.elapsed -= frame_time;
+
+            if self.frame + 1 < clip.len() {
+                self.frame += 1;
+            } else if clip.looping {
+                self.frame = 0;
+            } else {
+                self.finished = true;
+                if let Some(next) = &clip.next {
+                    self.play(next);
+                }
+                return;
+            }
+        }
+    }
+
+    fn sprite_index(&self, clip: &AnimationClip) -> usize {
+        clip.frames.0 + self.frame.min(clip.len() - 1)
+    }
+}
+
+/// Which way a sprite is looking. Sprite sheets are drawn facing right.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Facing {
+    Left,
+    #[default]
+    Right,
+}
+
+impl Facing {
+    /// Facing for a horizontal movement, if there is any.
+    pub fn from_movement(movement: f32) -> Option<Self> {
+        if movement < 0.0 {
+            Some(Self::Left)
+        } else if movement > 0.0 {
+            Some(Self::Right)
+        } else {
+            None
+        }
+    }
+
+    /// -1.0 when facing left, 1.0 when facing right.
+    pub fn sign(self) -> f32 {
+        match self {
+            Self::Left => -1.0,
+            Self::Right => 1.0,
+        }
+    }
+
+    /// Turns to face the other way, but only touches the component if it changes.
+    pub fn turn(facing: &mut Mut<Self>, towards: Self) {
+        if **facing != towards {
+            **facing = towards;
+        }
+    }
+}
+
+struct AnimationLoader;
+
+impl AssetLoader for AnimationLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let animations: AnimationSet = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(animations));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+fn play_animations(
+    time: Res<GameTime>,
+    animation_sets: Res<Assets<AnimationSet>>,
+    mut query: Query<(
+        &mut Animator,
+        &Handle<AnimationSet>,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    for (mut animator, handle, mut sprite) in query.iter_mut() {
+        let animations = match animation_sets.get(handle) {
+            Some(animations) => animations,
+            None => continue,
+        };
+
+        if let Some(clip) = animations.clips.get(animator.clip()) {
+            animator.advance(clip, time.delta_seconds());
+        }
+
+        // the clip may have changed to the next one, so look it up again
+        if let Some(clip) = animations.clips.get(animator.clip()) {
+            sprite.index = animator.sprite_index(clip);
+        }
+    }
+}
+
+type FlippedSprite = (
+    &'static Facing,
+    ChangeTrackers<Facing>,
+    Option<&'static mut TextureAtlasSprite>,
+    Option<&'static Children>,
+);
+
+/// Flips sprites to where they are facing, and mirrors their children (hitboxes, effects...)
+/// across the entity's origin along with them. Children don't face anywhere themselves, which
+/// keeps the two queries from both borrowing the same sprites.
+fn flip_to_facing(
+    mut query: Query<FlippedSprite, Changed<Facing>>,
+    mut children_query: Query<(&mut Transform, Option<&mut TextureAtlasSprite>), Without<Facing>>,
+) {
+    for (facing, tracker, sprite, children) in query.iter_mut() {
+        if let Some(mut sprite) = sprite {
+            sprite.flip_x = *facing == Facing::Left;
+        }
+
+        // children are laid out facing right, and mirrored on every turn after that
+        if tracker.is_added() && *facing == Facing::Right {
+            continue;
+        }
+
+        for &child in children.iter().flat_map(|children| children.iter()) {
+            if let Ok((mut transform, child_sprite)) = children_query.get_mut(child) {
+                let rotation = transform.rotation;
+                transform.translation.x = -transform.translation.x;
+                transform.rotation =
+                    Quat::from_xyzw(rotation.x, -rotation.y, -rotation.z, rotation.w);
+
+                if let Some(mut child_sprite) = child_sprite {
+                    child_sprite.flip_x = !child_sprite.flip_x;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_are_mirrored_on_every_turn() {
+        let mut world = World::new();
+        let child = world
+            .spawn()
+            .insert(Transform::from_xyz(5.0, 2.0, 0.0))
+            .insert(TextureAtlasSprite::default())
+            .id();
+        let parent = world
+            .spawn()
+            .insert(Facing::Right)
+            .insert(TextureAtlasSprite::default())
+            .push_children(&[child])
+            .id();
+        let mut stage = SystemStage::single_threaded().with_system(flip_to_facing);
+
+        // spawned facing right, as the children are laid out
+        stage.run(&mut world);
+        assert_eq!(world.get::<Transform>(child).unwrap().translation.x, 5.0);
+
+        for (facing, x) in [(Facing::Left, -5.0), (Facing::Right, 5.0)] {
+            *world.get_mut::<Facing>(parent).unwrap() = facing;
+            stage.run(&mut world);
+
+            let flipped = facing == Facing::Left;
+            assert_eq!(world.get::<Transform>(child).unwrap().translation.x, x);
+            assert_eq!(
+                world.get::<TextureAtlasSprite>(child).unwrap().flip_x,
+                flipped
+            );
+            assert_eq!(
+                world.get::<TextureAtlasSprite>(parent).unwrap().flip_x,
+                flipped
+            );
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioPlugin};
+use bevy_kira_audio::{Audio, AudioApp, AudioChannel, AudioPlugin};
 
+use crate::abilities::AbilityEvent;
 use crate::actions::Actions;
 use crate::loading::AudioAssets;
+use crate::player::PlayerSystem;
 use crate::GameState;
 
 pub struct InternalAudioPlugin;
@@ -11,13 +13,20 @@ pub struct InternalAudioPlugin;
 impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(AudioPlugin)
+            .add_audio_channel::<EffectsChannel>()
             .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_audio))
             .add_system_set(
-                SystemSet::on_update(GameState::Playing).with_system(control_flying_sound),
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(control_flying_sound)
+                    // on the tick the abilities are used, as their events don't outlast many frames
+                    .with_system(play_ability_sounds.after(PlayerSystem::Animation)),
             );
     }
 }
 
+/// One-shot sound effects, kept apart from the looping movement sound.
+struct EffectsChannel;
+
 fn start_audio(audio_assets: Res<AudioAssets>, audio: Res<Audio>) {
     audio.set_volume(0.3);
     // TODO: don't use this sound for movement, lol
@@ -32,3 +41,18 @@ fn control_flying_sound(actions: Res<Actions>, audio: Res<Audio>) {
         audio.pause()
     }
 }
+
+fn play_ability_sounds(
+    mut events: EventReader<AbilityEvent>,
+    audio_assets: Res<AudioAssets>,
+    effects: Res<AudioChannel<EffectsChannel>>,
+) {
+    for event in events.iter() {
+        match event {
+            // TODO: get an actual sound for this too
+            AbilityEvent::DoubleJump | AbilityEvent::WallJump | AbilityEvent::Dash => {
+                effects.play(audio_assets.flying.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::f32::consts;
+
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use heron::prelude::*;
+use serde::Deserialize;
+
+use crate::abilities::{Dash, DoubleJump, Glide, WallJump};
+use crate::actions::{Actions, ActionsSystem};
+use crate::animation::AnimationSet;
+use crate::kinematic::KinematicController;
+use crate::loading::{AnimationAssets, CharacterAssets, SpriteAssets};
+use crate::player::{MovementStats, Player, PlayerCollider, PlayerSystem};
+use crate::GameState;
+
+pub struct CharacterPlugin;
+
+/// This plugin lets the player swap between the chimeras in the [`Roster`]
+/// Swapping is only possible during the State `GameState::Playing`
+impl Plugin for CharacterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Roster>()
+            .add_asset::<CharacterDefinition>()
+            .add_asset_loader(CharacterLoader)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        swap_character
+                            .after(ActionsSystem::Read)
+                            .before(PlayerSystem::Movement),
+                    )
+                    .with_system(reload_characters.before(PlayerSystem::Movement)),
+            );
+    }
+}
+
+/// One of the animals Dr. Fingers has experimented on.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Character {
+    /// Part dog, part chicken: flaps for an extra jump and glides.
+    Dogken,
+    /// Part cat, part fish: clings to walls and jumps off them.
+    Catfish,
+}
+
+impl Character {
+    pub fn definition(self, characters: &CharacterAssets) -> &Handle<CharacterDefinition> {
+        match self {
+            Self::Dogken => &characters.dogken,
+            Self::Catfish => &characters.catfish,
+        }
+    }
+
+    pub fn collider_transform(self) -> Transform {
+        Transform::from_translation(Vec3::Y * 2.5)
+            .with_rotation(Quat::from_rotation_z(consts::PI / 2.0))
+            .with_scale(Vec3::splat(10.0))
+    }
+
+    pub fn atlas(self, sprites: &SpriteAssets) -> Handle<TextureAtlas> {
+        match self {
+            Self::Dogken => sprites.dogken.clone(),
+            Self::Catfish => sprites.catfish.clone(),
+        }
+    }
+
+    pub fn animations(self, animations: &AnimationAssets) -> Handle<AnimationSet> {
+        match self {
+            // TODO: the catfish will need its own clips along with its own sprites
+            Self::Dogken | Self::Catfish => animations.dogken.clone(),
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Self::Dogken => Color::WHITE,
+            // TODO: remove once the catfish has its own sprites
+            Self::Catfish => Color::rgb(0.6, 0.8, 1.0),
+        }
+    }
+
+    /// Gives an entity this character's abilities, taking away any others.
+    pub(crate) fn give_abilities(self, entity: &mut EntityCommands) {
+        entity
+            .remove::<DoubleJump>()
+            .remove::<Glide>()
+            .remove::<WallJump>()
+            .remove::<Dash>();
+
+        match self {
+            Self::Dogken => {
+                entity
+                    .insert(DoubleJump::new(1))
+                    .insert(Glide::default())
+                    .insert(Dash::new(true));
+            }
+            Self::Catfish => {
+                // dashes again once the cooldown is over, landed or not
+                entity.insert(WallJump::default()).insert(Dash::new(false));
+            }
+        }
+    }
+}
+
+/// Tunable values for a [`Character`], loaded from a `.character.ron` file.
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "78c3ebf0-22cc-4dd0-b714-89b51b7d1f2b"]
+pub struct CharacterDefinition {
+    pub movement: MovementStats,
+    pub collider: CapsuleDimensions,
+    /// Moves by collide-and-slide instead of as a dynamic body, if set.
+    #[serde(default)]
+    pub controller: Option<KinematicController>,
+}
+
+/// Size of the capsule collider, before it is laid on its side.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CapsuleDimensions {
+    pub half_segment: f32,
+    pub radius: f32,
+}
+
+impl CharacterDefinition {
+    /// The shape of the child [`PlayerCollider`].
+    pub fn collider(&self) -> CollisionShape {
+        // TODO: maybe trapezoidal convex hull instead?
+        CollisionShape::Capsule {
+            half_segment: self.collider.half_segment,
+            radius: self.collider.radius,
+        }
+    }
+
+    pub fn body(&self) -> RigidBody {
+        match self.controller {
+            Some(_) => RigidBody::KinematicPositionBased,
+            None => RigidBody::Dynamic,
+        }
+    }
+
+    /// Gives an entity this definition's body type and controller, if any.
+    pub(crate) fn give_controller(&self, entity: &mut EntityCommands) {
+        entity.insert(self.body());
+
+        match self.controller {
+            Some(controller) => entity.insert(controller),
+            None => entity.remove::<KinematicController>(),
+        };
+    }
+
+    /// Updates a player entity's stats, body and collider to match this definition.
+    fn apply(
+        &self,
+        entity: &mut EntityCommands,
+        stats: &mut MovementStats,
+        children: &Children,
+        collider_query: &mut Query<&mut CollisionShape, With<PlayerCollider>>,
+    ) {
+        *stats = self.movement;
+        self.give_controller(entity);
+
+        for &child in children.iter() {
+            if let Ok(mut shape) = collider_query.get_mut(child) {
+                *shape = self.collider();
+            }
+        }
+    }
+}
+
+struct CharacterLoader;
+
+impl AssetLoader for CharacterLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definition: CharacterDefinition = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definition));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["character.ron"]
+    }
+}
+
+/// The characters the player can swap between, in order.
+pub struct Roster {
+    pub characters: Vec<Character>,
+    active: usize,
+}
+
+impl Default for Roster {
+    fn default() -> Self {
+        Self {
+            characters: vec![Character::Dogken, Character::Catfish],
+            active: 0,
+        }
+    }
+}
+
+impl Roster {
+    pub fn active(&self) -> Character {
+        self.characters[self.active]
+    }
+
+    fn cycle(&mut self) -> Character {
+        self.active = (self.active + 1) % self.characters.len();
+        self.active()
+    }
+}
+
+type SwappablePlayer = (
+    Entity,
+    &'static mut Character,
+    &'static mut MovementStats,
+    &'static mut Handle<TextureAtlas>,
+    &'static mut Handle<AnimationSet>,
+    &'static mut TextureAtlasSprite,
+    &'static mut Velocity,
+    &'static Children,
+);
+
+/// Turns the player into the next character of the [`Roster`], right where they are.
+#[allow(clippy::too_many_arguments)]
+fn swap_character(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    sprites: Res<SpriteAssets>,
+    animations: Res<AnimationAssets>,
+    characters: Res<CharacterAssets>,
+    definitions: Res<Assets<CharacterDefinition>>,
+    mut roster: ResMut<Roster>,
+    mut player_query: Query<SwappablePlayer, With<Player>>,
+    mut collider_query: Query<&mut CollisionShape, With<PlayerCollider>>,
+) {
+    if !actions.player_swap {
+        return;
+    }
+
+    let next = roster.cycle();
+    let definition = definitions
+        .get(next.definition(&characters))
+        .expect("character definition not loaded");
+
+    for (
+        entity,
+        mut character,
+        mut stats,
+        mut atlas,
+        mut animation_set,
+        mut sprite,
+        mut velocity,
+        children,
+    ) in player_query.iter_mut()
+    {
+        *character = next;
+        definition.apply(
+            &mut commands.entity(entity),
+            &mut stats,
+            children,
+            &mut collider_query,
+        );
+        *atlas = next.atlas(&sprites);
+        *animation_set = next.animations(&animations);
+        sprite.color = next.color();
+
+        // keep the momentum, but no more than the new character could reach by running
+        velocity.linear.x = velocity
+            .linear
+            .x
+            .clamp(-stats.max_speed_x, stats.max_speed_x);
+
+        next.give_abilities(&mut commands.entity(entity));
+    }
+}
+
+/// Applies changes to a character definition file to the player while the game runs.
+fn reload_characters(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<CharacterDefinition>>,
+    characters: Res<CharacterAssets>,
+    definitions: Res<Assets<CharacterDefinition>>,
+    mut player_query: Query<(Entity, &Character, &mut MovementStats, &Children), With<Player>>,
+    mut collider_query: Query<&mut CollisionShape, With<PlayerCollider>>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Modified { handle } => handle,
+            _ => continue,
+        };
+
+        for (entity, character, mut stats, children) in player_query.iter_mut() {
+            if character.definition(&characters) != handle {
+                continue;
+            }
+
+            if let Some(definition) = definitions.get(handle) {
+                definition.apply(
+                    &mut commands.entity(entity),
+                    &mut stats,
+                    children,
+                    &mut collider_query,
+                );
+            }
+        }
+    }
+}
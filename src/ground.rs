@@ -0,0 +1,140 @@
+use bevy::ecs::schedule::StageLabel;
+use bevy::prelude::*;
+use heron::prelude::*;
+use heron::rapier_plugin::{PhysicsWorld, ShapeCastCollisionType};
+
+pub struct GroundPlugin;
+
+/// This plugin looks for ground under everything with a [`GroundProbe`], so movement code
+/// can check [`Grounded`] instead of sorting through contacts itself
+impl Plugin for GroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_stage_before(
+            CoreStage::Update,
+            GroundStage,
+            SystemStage::single_threaded().with_system(probe_ground),
+        );
+    }
+}
+
+/// Probes for ground right before the update stage, so movement sees the ground as the last
+/// physics step left it. It has a stage of its own as `Grounded` is inserted by commands.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct GroundStage;
+
+/// Casts the entity's collider (its own, or a child's) a short way down to look for ground.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GroundProbe {
+    /// How far below the collider ground is still found.
+    pub distance: f32,
+    /// Steepest slope, in radians, that still counts as ground and not a wall.
+    pub max_slope: f32,
+}
+
+impl Default for GroundProbe {
+    fn default() -> Self {
+        Self {
+            distance: 4.0,
+            max_slope: 50f32.to_radians(),
+        }
+    }
+}
+
+/// What an entity with a [`GroundProbe`] is standing on, if anything.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Grounded {
+    /// The collision shape entity found below.
+    pub entity: Entity,
+    /// Surface normal of the ground, pointing up out of it.
+    pub normal: Vec2,
+    /// Gap between the collider and the ground.
+    pub distance: f32,
+}
+
+/// The cast shape is shrunk by this much, so a collider resting on the ground is not
+/// already touching it at the start of the cast.
+const SKIN: f32 = 1.0;
+
+fn probe_ground(
+    mut commands: Commands,
+    physics_world: PhysicsWorld,
+    probes: Query<(Entity, &GroundProbe, Option<&Children>)>,
+    shapes: Query<(&CollisionShape, &GlobalTransform)>,
+    bodies: Query<&RigidBody>,
+) {
+    for (entity, probe, children) in probes.iter() {
+        let collider = std::iter::once(entity)
+            .chain(
+                children
+                    .iter()
+                    .flat_map(|children| children.iter().copied()),
+            )
+            .find(|&entity| shapes.contains(entity));
+
+        let (collider, (shape, transform)) = match collider {
+            Some(collider) => (collider, shapes.get(collider).unwrap()),
+            None => continue,
+        };
+
+        let hit = physics_world.shape_cast_with_filter(
+            &shrink(shape, SKIN),
+            transform.translation,
+            transform.rotation,
+            -Vec3::Y * (probe.distance + SKIN),
+            CollisionLayers::default(),
+            |other| {
+                other != entity
+                    && other != collider
+                    && !matches!(bodies.get(other), Ok(RigidBody::Sensor))
+            },
+        );
+
+        let grounded = hit.and_then(|hit| match hit.collision_type {
+            ShapeCastCollisionType::Collided(info) => {
+                // heron swaps the sides rapier reports: `self_normal` is the hit collider's,
+                // in world space, while `other_normal` is the cast shape's own
+                let normal = info.self_normal.truncate();
+                (normal.y >= probe.max_slope.cos()).then(|| Grounded {
+                    entity: hit.entity,
+                    normal,
+                    distance: (transform.translation.y - info.self_end_position.y - SKIN).max(0.0),
+                })
+            }
+            // sunk into something, e.g. after landing hard: no normal, but still on it
+            ShapeCastCollisionType::AlreadyPenetrating => Some(Grounded {
+                entity: hit.entity,
+                normal: Vec2::Y,
+                distance: 0.0,
+            }),
+        });
+
+        match grounded {
+            Some(grounded) => commands.entity(entity).insert(grounded),
+            None => commands.entity(entity).remove::<Grounded>(),
+        };
+    }
+}
+
+/// The same shape, smaller by `by` on every side.
+pub(crate) fn shrink(shape: &CollisionShape, by: f32) -> CollisionShape {
+    match *shape {
+        CollisionShape::Capsule {
+            half_segment,
+            radius,
+        } => CollisionShape::Capsule {
+            half_segment,
+            radius: (radius - by).max(0.0),
+        },
+        CollisionShape::Cuboid {
+            half_extends,
+            border_radius,
+        } => CollisionShape::Cuboid {
+            half_extends: (half_extends - Vec3::splat(by)).max(Vec3::ZERO),
+            border_radius,
+        },
+        CollisionShape::Sphere { radius } => CollisionShape::Sphere {
+            radius: (radius - by).max(0.0),
+        },
+        ref shape => shape.clone(),
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::timestep::GameTime;
+use crate::GameState;
+
+pub struct HealthPlugin;
+
+/// This plugin applies [`DamageEvent`]s to anything with [`Health`], the player or otherwise
+/// Whatever runs out of health is [`Dying`] for a moment, then respawns or is despawned
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(take_damage.label(HealthSystem::Damage))
+                .with_system(flicker.after(HealthSystem::Damage))
+                .with_system(finish_dying.after(HealthSystem::Damage)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum HealthSystem {
+    /// Send [`DamageEvent`]s before this to apply them on the same frame.
+    Damage,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+    /// Seconds of invulnerability after taking a hit.
+    pub invulnerable_time: f32,
+    /// Seconds between running out of health and respawning.
+    pub death_time: f32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self {
+            current: max,
+            max,
+            invulnerable_time: 1.0,
+            death_time: 1.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+}
+
+/// Something hurt `target`.
+pub struct DamageEvent {
+    pub target: Entity,
+    /// What dealt the damage, e.g. a hazard tile.
+    pub source: Option<Entity>,
+    pub amount: u32,
+    /// Velocity the target is sent flying with.
+    pub knockback: Vec2,
+}
+
+/// Ignores damage until the timer runs out, flickering meanwhile.
+#[derive(Component)]
+pub struct Invulnerable(Timer);
+
+/// Ran out of health, and waiting to respawn.
+#[derive(Component)]
+pub struct Dying(Timer);
+
+/// Where an entity comes back after dying. Entities without one stay dead.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Respawn {
+    pub point: Vec3,
+}
+
+const FLICKER_PERIOD: f32 = 0.1;
+
+type Vulnerable = (Without<Invulnerable>, Without<Dying>);
+
+type DyingEntity = (
+    Entity,
+    &'static mut Dying,
+    &'static mut Health,
+    &'static mut Transform,
+    Option<&'static mut Velocity>,
+    Option<&'static Respawn>,
+);
+
+fn take_damage(
+    mut commands: Commands,
+    mut events: EventReader<DamageEvent>,
+    mut query: Query<(&mut Health, Option<&mut Velocity>), Vulnerable>,
+) {
+    // invulnerability only takes effect once commands are applied, so keep track here too
+    let mut hit = HashSet::new();
+
+    for event in events.iter() {
+        let (mut health, velocity) = match query.get_mut(event.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+
+        if health.is_dead() || !hit.insert(event.target) {
+            continue;
+        }
+
+        debug!(
+            "{:?} took {} damage from {:?}",
+            event.target, event.amount, event.source
+        );
+        health.current = health.current.saturating_sub(event.amount);
+
+        if let Some(mut velocity) = velocity {
+            velocity.linear = event.knockback.extend(0.0);
+        }
+
+        let mut target = commands.entity(event.target);
+        if health.is_dead() {
+            target.insert(Dying(Timer::from_seconds(health.death_time, false)));
+        } else {
+            target.insert(Invulnerable(Timer::from_seconds(
+                health.invulnerable_time,
+                false,
+            )));
+        }
+    }
+}
+
+fn flicker(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    mut query: Query<(Entity, &mut Invulnerable, &mut Visibility)>,
+) {
+    for (entity, mut invulnerable, mut visibility) in query.iter_mut() {
+        invulnerable.0.tick(time.delta());
+
+        if invulnerable.0.finished() {
+            visibility.is_visible = true;
+            commands.entity(entity).remove::<Invulnerable>();
+        } else {
+            let blinks = (invulnerable.0.elapsed_secs() / FLICKER_PERIOD) as u32;
+            visibility.is_visible = blinks.is_multiple_of(2);
+        }
+    }
+}
+
+fn finish_dying(mut commands: Commands, time: Res<GameTime>, mut query: Query<DyingEntity>) {
+    for (entity, mut dying, mut health, mut transform, velocity, respawn) in query.iter_mut() {
+        if !dying.0.tick(time.delta()).finished() {
+            continue;
+        }
+
+        let respawn = match respawn {
+            Some(respawn) => respawn,
+            None => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+
+        transform.translation = respawn.point;
+        if let Some(mut velocity) = velocity {
+            *velocity = Velocity::default();
+        }
+        health.current = health.max;
+
+        commands
+            .entity(entity)
+            .remove::<Dying>()
+            .insert(Invulnerable(Timer::from_seconds(
+                health.invulnerable_time,
+                false,
+            )));
+    }
+}
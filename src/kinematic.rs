@@ -0,0 +1,251 @@
+use bevy::prelude::*;
+use heron::prelude::*;
+use heron::rapier_plugin::{PhysicsWorld, ShapeCastCollisionType};
+use serde::Deserialize;
+
+use crate::ground::{self, Grounded};
+use crate::player::PlayerSystem;
+use crate::timestep::GameTime;
+use crate::GameState;
+
+pub struct KinematicPlugin;
+
+/// This plugin moves kinematic bodies with a [`KinematicController`] by their `Velocity`,
+/// sliding along whatever they run into instead of leaving it to the physics engine
+impl Plugin for KinematicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(
+                move_kinematic
+                    .label(KinematicSystem::Move)
+                    .after(PlayerSystem::Movement),
+            ),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum KinematicSystem {
+    /// Anything changing the velocity of a kinematic body should run before this.
+    Move,
+}
+
+/// Collide-and-slide movement for a `RigidBody::KinematicPositionBased`.
+#[derive(Component, Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct KinematicController {
+    /// Tallest ledge walked up onto without jumping.
+    pub max_step_height: f32,
+    /// Steepest slope, in degrees, that can be walked up.
+    pub max_slope: f32,
+    /// How far the ground can drop away while walking before the body starts to fall.
+    pub snap_distance: f32,
+    /// Gap kept between the collider and whatever it slides along.
+    pub skin: f32,
+}
+
+impl Default for KinematicController {
+    fn default() -> Self {
+        Self {
+            max_step_height: 12.0,
+            max_slope: 50.0,
+            snap_distance: 8.0,
+            skin: 0.5,
+        }
+    }
+}
+
+/// Each hit takes away part of the motion, so this is plenty for a corner or two.
+const MAX_SLIDES: usize = 4;
+
+type KinematicBody = (
+    Entity,
+    &'static KinematicController,
+    &'static mut Transform,
+    &'static mut Velocity,
+    &'static Acceleration,
+    Option<&'static Grounded>,
+    &'static Children,
+);
+
+/// A shape cast that ignores the moving body itself and any sensors.
+struct Caster<'a, 'w, 's> {
+    physics_world: &'a PhysicsWorld<'w, 's>,
+    bodies: &'a Query<'w, 's, &'static RigidBody>,
+    shape: CollisionShape,
+    rotation: Quat,
+    ignored: [Entity; 2],
+    skin: f32,
+}
+
+/// Where a cast stopped, and what it hit.
+struct Hit {
+    entity: Entity,
+    travel: f32,
+    normal: Vec2,
+}
+
+impl Caster<'_, '_, '_> {
+    /// How far the collider can move from `from` along `motion`, keeping the skin clear.
+    fn cast(&self, from: Vec2, motion: Vec2) -> Option<Hit> {
+        let distance = motion.length();
+        if distance <= f32::EPSILON {
+            return None;
+        }
+
+        let hit = self.physics_world.shape_cast_with_filter(
+            &self.shape,
+            from.extend(0.0),
+            self.rotation,
+            (motion / distance * (distance + self.skin)).extend(0.0),
+            CollisionLayers::default(),
+            |other| {
+                !self.ignored.contains(&other)
+                    && !matches!(self.bodies.get(other), Ok(RigidBody::Sensor))
+            },
+        )?;
+
+        match hit.collision_type {
+            ShapeCastCollisionType::Collided(info) => Some(Hit {
+                entity: hit.entity,
+                travel: ((info.self_end_position.truncate() - from).length() - self.skin).max(0.0),
+                normal: info.other_normal.truncate().normalize_or_zero(),
+            }),
+            // already stuck in something: let the body move freely until it is out again
+            ShapeCastCollisionType::AlreadyPenetrating => None,
+        }
+    }
+
+    /// Tries to climb a ledge of at most `height` in the way of `motion`, returning where
+    /// the collider ends up on top of it.
+    fn step_up(&self, from: Vec2, motion: Vec2, height: f32, min_floor_y: f32) -> Option<Vec2> {
+        let up = Vec2::Y * height;
+        let raised = from + self.cast(from, up).map_or(up, |hit| Vec2::Y * hit.travel);
+
+        let forward = self
+            .cast(raised, motion)
+            .map_or(motion, |hit| motion.normalize_or_zero() * hit.travel);
+        if forward.length() <= self.skin {
+            return None;
+        }
+
+        let ahead = raised + forward;
+        let drop = Vec2::Y * -(raised.y - from.y + self.skin);
+        let landing = self.cast(ahead, drop)?;
+
+        (landing.normal.y >= min_floor_y).then(|| ahead + drop.normalize() * landing.travel)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn move_kinematic(
+    time: Res<GameTime>,
+    gravity: Res<Gravity>,
+    physics_world: PhysicsWorld,
+    mut query: Query<KinematicBody>,
+    shapes: Query<(&CollisionShape, &Transform), Without<KinematicController>>,
+    bodies: Query<&'static RigidBody>,
+    mut pushed: Query<&mut Velocity, Without<KinematicController>>,
+) {
+    let delta = time.delta_seconds();
+
+    for (entity, controller, mut transform, mut velocity, acceleration, grounded, children) in
+        query.iter_mut()
+    {
+        let collider = children.iter().find_map(|&child| {
+            shapes
+                .get(child)
+                .ok()
+                .map(|(shape, offset)| (child, shape, *offset))
+        });
+        let (collider, shape, offset) = match collider {
+            Some(collider) => collider,
+            None => continue,
+        };
+
+        let pose = transform.mul_transform(offset);
+        let caster = Caster {
+            physics_world: &physics_world,
+            bodies: &bodies,
+            shape: ground::shrink(shape, controller.skin),
+            rotation: pose.rotation,
+            ignored: [entity, collider],
+            skin: controller.skin,
+        };
+        let min_floor_y = controller.max_slope.to_radians().cos();
+
+        // the physics engine doesn't move kinematic bodies, so gravity is up to us too
+        velocity.linear += (gravity.vector() + acceleration.linear) * delta;
+        let was_grounded =
+            grounded.is_some_and(|grounded| velocity.linear.truncate().dot(grounded.normal) <= 0.0);
+
+        let start = pose.translation.truncate();
+        let mut position = start;
+        let mut motion = velocity.linear.truncate() * delta;
+        let mut slide_velocity = velocity.linear.truncate();
+        let mut pushes = Vec::new();
+
+        for _ in 0..MAX_SLIDES {
+            let hit = match caster.cast(position, motion) {
+                Some(hit) => hit,
+                None => {
+                    position += motion;
+                    break;
+                }
+            };
+
+            let direction = motion.normalize_or_zero();
+            position += direction * hit.travel;
+            let mut remaining = motion - direction * hit.travel;
+
+            // the physics engine doesn't push anything out of the way of a kinematic body
+            if matches!(bodies.get(hit.entity), Ok(RigidBody::Dynamic))
+                && hit.normal.y.abs() < min_floor_y
+            {
+                pushes.push((hit.entity, slide_velocity.x));
+            }
+
+            let mut normal = hit.normal;
+            if normal.y < min_floor_y {
+                if was_grounded && normal.y.abs() < min_floor_y {
+                    let across = Vec2::new(remaining.x, 0.0);
+                    if let Some(stepped) =
+                        caster.step_up(position, across, controller.max_step_height, min_floor_y)
+                    {
+                        position = stepped;
+                        break;
+                    }
+                }
+
+                // too steep to walk up, so it blocks like a wall instead
+                if normal.y > 0.0 {
+                    normal = Vec2::new(normal.x, 0.0).normalize_or_zero();
+                }
+            }
+
+            remaining -= normal * remaining.dot(normal);
+            slide_velocity -= normal * slide_velocity.dot(normal).min(0.0);
+            motion = remaining;
+        }
+
+        // keep walking down slopes and off small steps, rather than launching off them
+        if was_grounded {
+            let snap = Vec2::Y * -controller.snap_distance;
+            if let Some(hit) = caster.cast(position, snap) {
+                if hit.normal.y >= min_floor_y {
+                    position.y -= hit.travel;
+                    slide_velocity.y = slide_velocity.y.max(0.0);
+                }
+            }
+        }
+
+        transform.translation += (position - start).extend(0.0);
+        velocity.linear = slide_velocity.extend(velocity.linear.z);
+
+        for (entity, speed) in pushes {
+            if let Ok(mut pushed) = pushed.get_mut(entity) {
+                pushed.linear.x = speed;
+            }
+        }
+    }
+}
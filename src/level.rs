@@ -1,34 +1,193 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
 use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
 use bevy::reflect::TypeUuid;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 use bevy::{log, prelude::*};
 use heron::prelude::*;
 
 use crate::loading::{MapAssets, TileAssets};
+use crate::props::{PressurePlate, PLATE_COLOR};
 use crate::GameState;
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_asset::<Level>()
+        app.init_resource::<LevelGrid>()
+            .init_resource::<LevelStreaming>()
+            .init_resource::<LoadedChunks>()
+            .init_resource::<SlopeMeshes>()
+            .add_asset::<Level>()
             .add_asset_loader(LevelLoader)
-            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_level));
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(stream_chunks.label(LevelSystem::Stream)),
+            );
     }
 }
 
-#[derive(Component, Clone, Copy)]
+/// Streams `level` around the [`ChunkLoader`]s on every update like in the game, but without
+/// its loaded assets or states, so the level_streaming benchmark can run it on its own.
+/// The app needs the `AssetPlugin`.
+pub fn add_streaming(app: &mut App, level: Level) {
+    app.add_asset::<Level>()
+        .add_asset::<Mesh>()
+        .add_asset::<ColorMaterial>();
+    let level0 = app.world.resource_mut::<Assets<Level>>().add(level);
+
+    app.insert_resource(MapAssets { level0 })
+        .insert_resource(TileAssets {
+            tiles: Handle::default(),
+        })
+        .init_resource::<LevelGrid>()
+        .init_resource::<LevelStreaming>()
+        .init_resource::<LoadedChunks>()
+        .init_resource::<SlopeMeshes>()
+        .add_system(stream_chunks);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum LevelSystem {
+    /// Spawns and despawns chunks around the [`ChunkLoader`]s.
+    Stream,
+}
+
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Tile {
+    #[default]
     Empty,
     Floor(Side),
+    /// Solid ground that can be walked up and down.
+    Slope(Slope),
+    /// Climbable, but not solid: the player passes through it unless climbing.
+    Ladder,
+    /// A volume the player can swim through.
+    Liquid(Liquid),
+    /// Solid block blowing an [`Updraft`] upwards, until the next solid tile.
+    Vent,
+    /// Where the player respawns after dying, once they have touched it.
+    Checkpoint,
+    /// Where a [`Crate`](crate::props::Crate) starts out. It is not spawned with the
+    /// chunk, as it can be pushed out of it.
+    Crate,
+    /// A [`PressurePlate`] on the bottom of the cell.
+    PressurePlate,
 }
 
-impl Default for Tile {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Liquid {
+    Water,
+    /// Swims like water, but hurts to touch.
+    Acid,
+}
+
+impl Liquid {
+    fn color(self) -> Color {
+        match self {
+            Self::Water => Color::rgba(0.2, 0.4, 0.9, 0.5),
+            Self::Acid => Color::rgba(0.4, 0.9, 0.1, 0.6),
+        }
+    }
+}
+
+/// Marks things the player should not touch.
+#[derive(Component)]
+pub struct Hazard;
+
+/// A column of rising air above a [`Tile::Vent`], which lifts gliding players.
+#[derive(Component, Clone, Copy)]
+pub struct Updraft {
+    /// Upward acceleration applied to a gliding player.
+    pub strength: f32,
+    /// Fastest a gliding player can be lifted.
+    pub max_speed: f32,
+}
+
+impl Default for Updraft {
     fn default() -> Self {
-        todo!()
+        Self {
+            strength: 600.0,
+            max_speed: 150.0,
+        }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Tile {
+    /// Whether the player collides with this tile.
+    pub fn is_solid(self) -> bool {
+        matches!(self, Self::Floor(_) | Self::Slope(_) | Self::Vent)
+    }
+
+    /// The tile for a character of the `.lvl` format. Unknown characters are [`Tile::Empty`].
+    pub fn from_glyph(c: char) -> Self {
+        match c {
+            '[' => Self::Floor(Side::Left),
+            '=' => Self::Floor(Side::Middle),
+            ']' => Self::Floor(Side::Right),
+            '¬' => Self::Floor(Side::TopRight),
+            '4' => Self::Floor(Side::TopLeft),
+            '-' => Self::Floor(Side::Top),
+            'L' => Self::Floor(Side::BotLeft),
+            '_' => Self::Floor(Side::Bot),
+            '/' => Self::Floor(Side::BotRight),
+            '•' => Self::Floor(Side::Standalone),
+            '◢' => Self::Slope(Slope::SteepRight),
+            '◣' => Self::Slope(Slope::SteepLeft),
+            's' => Self::Slope(Slope::ShallowRightLow),
+            'S' => Self::Slope(Slope::ShallowRightMid),
+            '$' => Self::Slope(Slope::ShallowRightHigh),
+            'z' => Self::Slope(Slope::ShallowLeftLow),
+            'Z' => Self::Slope(Slope::ShallowLeftMid),
+            '2' => Self::Slope(Slope::ShallowLeftHigh),
+            'H' => Self::Ladder,
+            '~' => Self::Liquid(Liquid::Water),
+            '%' => Self::Liquid(Liquid::Acid),
+            '^' => Self::Vent,
+            'P' => Self::Checkpoint,
+            'C' => Self::Crate,
+            'o' => Self::PressurePlate,
+            _ => Self::Empty,
+        }
+    }
+
+    /// The character representing this tile in the `.lvl` format.
+    pub fn glyph(self) -> char {
+        match self {
+            Self::Empty => ' ',
+            Self::Floor(Side::Left) => '[',
+            Self::Floor(Side::Middle) => '=',
+            Self::Floor(Side::Right) => ']',
+            Self::Floor(Side::TopRight) => '¬',
+            Self::Floor(Side::TopLeft) => '4',
+            Self::Floor(Side::Top) => '-',
+            Self::Floor(Side::BotLeft) => 'L',
+            Self::Floor(Side::Bot) => '_',
+            Self::Floor(Side::BotRight) => '/',
+            Self::Floor(Side::Standalone) => '•',
+            Self::Slope(Slope::SteepRight) => '◢',
+            Self::Slope(Slope::SteepLeft) => '◣',
+            Self::Slope(Slope::ShallowRightLow) => 's',
+            Self::Slope(Slope::ShallowRightMid) => 'S',
+            Self::Slope(Slope::ShallowRightHigh) => '$',
+            Self::Slope(Slope::ShallowLeftLow) => 'z',
+            Self::Slope(Slope::ShallowLeftMid) => 'Z',
+            Self::Slope(Slope::ShallowLeftHigh) => '2',
+            Self::Ladder => 'H',
+            Self::Liquid(Liquid::Water) => '~',
+            Self::Liquid(Liquid::Acid) => '%',
+            Self::Vent => '^',
+            Self::Checkpoint => 'P',
+            Self::Crate => 'C',
+            Self::PressurePlate => 'o',
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Side {
     // Height=2 blocks
     TopLeft,
@@ -64,23 +223,394 @@ impl Side {
     }
 }
 
-const LEVEL_WIDTH: usize = 20;
-const LEVEL_HEIGHT: usize = 14;
+/// Which way a slope tile rises, and how steeply.
+///
+/// Shallow slopes rise at 22.5°, which takes √2 + 1 (about 2.41) tiles to climb one, so
+/// they are laid out as a ramp of a low, a middle and a high tile. The high tile levels off
+/// at the top for the rest of its width.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Slope {
+    /// 45°, rising to the right.
+    SteepRight,
+    /// 45°, rising to the left.
+    SteepLeft,
+    ShallowRightLow,
+    ShallowRightMid,
+    ShallowRightHigh,
+    ShallowLeftLow,
+    ShallowLeftMid,
+    ShallowLeftHigh,
+}
+
+impl Slope {
+    pub const ALL: [Self; 8] = [
+        Self::SteepRight,
+        Self::SteepLeft,
+        Self::ShallowRightLow,
+        Self::ShallowRightMid,
+        Self::ShallowRightHigh,
+        Self::ShallowLeftLow,
+        Self::ShallowLeftMid,
+        Self::ShallowLeftHigh,
+    ];
+
+    /// Rise of a shallow slope over one tile.
+    pub const SHALLOW_RISE: f32 = std::f32::consts::SQRT_2 - 1.0;
+
+    /// Corners of the solid part of the tile, counter-clockwise, for a tile of size 1
+    /// centred on the origin.
+    pub fn outline(self) -> Vec<Vec2> {
+        let rise = Self::SHALLOW_RISE;
+        let rising_right = match self {
+            Self::SteepRight | Self::SteepLeft => vec![
+                Vec2::new(-0.5, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0.5, 0.5),
+            ],
+            Self::ShallowRightLow | Self::ShallowLeftLow => vec![
+                Vec2::new(-0.5, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0.5, -0.5 + rise),
+            ],
+            Self::ShallowRightMid | Self::ShallowLeftMid => vec![
+                Vec2::new(-0.5, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0.5, -0.5 + 2.0 * rise),
+                Vec2::new(-0.5, -0.5 + rise),
+            ],
+            // the ramp reaches the top a little way into the tile
+            Self::ShallowRightHigh | Self::ShallowLeftHigh => vec![
+                Vec2::new(-0.5, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0.5, 0.5),
+                Vec2::new(-0.5 + (1.0 / rise - 2.0), 0.5),
+                Vec2::new(-0.5, -0.5 + 2.0 * rise),
+            ],
+        };
+
+        match self {
+            Self::SteepRight
+            | Self::ShallowRightLow
+            | Self::ShallowRightMid
+            | Self::ShallowRightHigh => rising_right,
+            // mirrored, and reversed to stay counter-clockwise
+            _ => rising_right
+                .into_iter()
+                .rev()
+                .map(|corner| Vec2::new(-corner.x, corner.y))
+                .collect(),
+        }
+    }
+}
+
+const LADDER_INDEX: usize = 10;
 
-#[derive(TypeUuid)]
+/// How many cells above a vent its updraft can reach.
+const VENT_REACH: usize = 4;
+
+// TODO: use a flag sprite instead of a plain post
+const CHECKPOINT_COLOR: Color = Color::rgb(0.9, 0.75, 0.2);
+
+/// A grid of tiles, indexed by column `x` and row `y`, with row 0 at the top.
+///
+/// Levels are parsed from and written back to the `.lvl` text format with [`FromStr`]
+/// and [`Display`](fmt::Display): one line per row, one [`Tile::glyph`] per column.
+/// The width is only kept in the rows, so a level without any is read back as 0x0.
+#[derive(TypeUuid, Debug, Clone, PartialEq, Eq)]
 #[uuid = "e89843e3-8db2-4467-ae09-196b0bb31aa9"]
 pub struct Level {
     tiles: Vec<Vec<Tile>>,
+    width: usize,
+}
+
+impl Level {
+    /// An empty level of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            tiles: vec![vec![Tile::Empty; width]; height],
+            width,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// The tile at the given cell, or `None` if it is outside the level.
+    pub fn get(&self, x: usize, y: usize) -> Option<Tile> {
+        self.tiles.get(y)?.get(x).copied()
+    }
+
+    /// Replace the tile at the given cell.
+    ///
+    /// # Panics
+    ///
+    /// If the cell is outside the level.
+    pub fn set(&mut self, x: usize, y: usize, tile: Tile) {
+        let (width, height) = (self.width(), self.height());
+        let cell = self
+            .tiles
+            .get_mut(y)
+            .and_then(|row| row.get_mut(x))
+            .unwrap_or_else(|| panic!("({x}, {y}) is outside the {width}x{height} level"));
+
+        *cell = tile;
+    }
+
+    /// All cells of the level, row by row.
+    pub fn tiles(&self) -> impl Iterator<Item = (usize, usize, Tile)> + '_ {
+        self.tiles
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &tile)| (x, y, tile)))
+    }
+
+    /// All cells the player can collide with.
+    pub fn solid_cells(&self) -> impl Iterator<Item = (usize, usize, Tile)> + '_ {
+        self.tiles().filter(|&(_, _, tile)| tile.is_solid())
+    }
+}
+
+impl FromStr for Level {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let height = input.lines().count();
+        let width = input
+            .lines()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut level = Level::new(width, height);
+
+        for (y, row) in input.lines().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                level.set(x, y, Tile::from_glyph(c));
+            }
+        }
+
+        Ok(level)
+    }
+}
+
+impl fmt::Display for Level {
+    /// Rows are padded to the full width, so the size survives a round trip.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in &self.tiles {
+            for tile in row {
+                write!(f, "{}", tile.glyph())?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps between level cells and world positions, independently of the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelGrid {
+    /// Side length of a single tile, in world units.
+    pub tile_size: f32,
+    /// World position of the centre of cell `(0, 0)`.
+    pub origin: Vec2,
+}
+
+impl Default for LevelGrid {
+    /// Puts the top-left corner of the level at the top-left of an 800x600 window.
+    fn default() -> Self {
+        Self {
+            tile_size: 48.0,
+            origin: Vec2::new(-400.0, 300.0),
+        }
+    }
+}
+
+impl LevelGrid {
+    /// World position of the centre of a cell.
+    pub fn cell_to_world(&self, x: usize, y: usize) -> Vec2 {
+        self.origin + Vec2::new(x as f32, -(y as f32)) * self.tile_size
+    }
+
+    /// The cell containing a world position, or `None` if it is left of or above the level.
+    pub fn world_to_cell(&self, position: Vec2) -> Option<(usize, usize)> {
+        let offset = (position - self.origin) / self.tile_size;
+        let (x, y) = ((offset.x + 0.5).floor(), (-offset.y + 0.5).floor());
+
+        if x < 0.0 || y < 0.0 {
+            None
+        } else {
+            Some((x as usize, y as usize))
+        }
+    }
+
+    /// The bottom-left and top-right corners of a level, in world space.
+    pub fn level_bounds(&self, level: &Level) -> (Vec2, Vec2) {
+        let half_tile = self.tile_size / 2.0;
+        let top_left = self.cell_to_world(0, 0) + Vec2::new(-half_tile, half_tile);
+        let size = Vec2::new(level.width() as f32, level.height() as f32) * self.tile_size;
+
+        (
+            Vec2::new(top_left.x, top_left.y - size.y),
+            Vec2::new(top_left.x + size.x, top_left.y),
+        )
+    }
+}
+
+/// Marks a camera whose view, plus [`LevelStreaming::margin`], is kept spawned.
+#[derive(Component)]
+pub struct ChunkLoader;
+
+/// Splits the level into square chunks that are only spawned near a [`ChunkLoader`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelStreaming {
+    /// Side length of a chunk, in cells.
+    pub chunk_size: usize,
+    /// Distance around the view, in world units, that is also kept spawned.
+    pub margin: f32,
 }
 
-impl Default for Level {
+impl Default for LevelStreaming {
     fn default() -> Self {
         Self {
-            tiles: vec![vec![Tile::Empty; LEVEL_WIDTH]; LEVEL_HEIGHT],
+            chunk_size: 8,
+            margin: 96.0,
         }
     }
 }
 
+impl LevelStreaming {
+    /// Chunks of the level overlapping a world-space rectangle grown by the margin.
+    pub fn chunks_in_view(
+        &self,
+        grid: &LevelGrid,
+        level: &Level,
+        view_min: Vec2,
+        view_max: Vec2,
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let margin = Vec2::splat(self.margin);
+        // cell rows grow downwards, so the top-left corner of the view has the lowest cell
+        let to_cell = |position: Vec2| {
+            ((position - grid.origin) / grid.tile_size * Vec2::new(1.0, -1.0) + 0.5).floor()
+        };
+        let first = to_cell(Vec2::new(view_min.x, view_max.y) + margin * Vec2::new(-1.0, 1.0));
+        let last = to_cell(Vec2::new(view_max.x, view_min.y) + margin * Vec2::new(1.0, -1.0));
+
+        let size = Vec2::new(level.width() as f32, level.height() as f32);
+        let (xs, ys) = if last.x < 0.0 || last.y < 0.0 || first.x >= size.x || first.y >= size.y {
+            (0..0, 0..0)
+        } else {
+            let first = first.max(Vec2::ZERO);
+            let last = last.min(size - 1.0);
+            (
+                first.x as usize / self.chunk_size..last.x as usize / self.chunk_size + 1,
+                first.y as usize / self.chunk_size..last.y as usize / self.chunk_size + 1,
+            )
+        };
+
+        ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+    }
+
+    /// The chunk a cell belongs to.
+    pub fn chunk_of(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        (x / self.chunk_size, y / self.chunk_size)
+    }
+
+    /// The non-empty cells of a chunk, which each get their own entity when spawned.
+    pub fn chunk_tiles<'a>(
+        &self,
+        level: &'a Level,
+        (chunk_x, chunk_y): (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize, Tile)> + 'a {
+        let xs = chunk_x * self.chunk_size..(chunk_x + 1) * self.chunk_size;
+        let ys = chunk_y * self.chunk_size..(chunk_y + 1) * self.chunk_size;
+
+        ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+            .filter_map(move |(x, y)| Some((x, y, level.get(x, y)?)))
+            .filter(|&(_, _, tile)| tile != Tile::Empty)
+    }
+}
+
+/// One mesh per kind of slope, since there is no art for them in the tile set yet.
+struct SlopeMeshes {
+    meshes: HashMap<Slope, Mesh2dHandle>,
+    material: Handle<ColorMaterial>,
+}
+
+// TODO: draw slopes from the tile set once it has them
+const SLOPE_COLOR: Color = Color::rgb(0.33, 0.33, 0.38);
+
+impl FromWorld for SlopeMeshes {
+    fn from_world(world: &mut World) -> Self {
+        let tile_size = world.get_resource::<LevelGrid>().unwrap().tile_size;
+
+        let mut mesh_assets = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+        let meshes = Slope::ALL
+            .into_iter()
+            .map(|slope| {
+                let outline: Vec<_> = slope
+                    .outline()
+                    .into_iter()
+                    .map(|corner| corner * tile_size)
+                    .collect();
+                (slope, mesh_assets.add(polygon_mesh(&outline)).into())
+            })
+            .collect();
+
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let material = materials.add(SLOPE_COLOR.into());
+
+        Self { meshes, material }
+    }
+}
+
+/// A flat mesh filling a convex polygon.
+fn polygon_mesh(corners: &[Vec2]) -> Mesh {
+    let positions: Vec<_> = corners.iter().map(|c| [c.x, c.y, 0.0]).collect();
+    let normals = vec![[0.0, 0.0, 1.0]; corners.len()];
+    let uvs: Vec<_> = corners.iter().map(|c| [c.x, -c.y]).collect();
+    let indices = (1..corners.len() as u32 - 1)
+        .flat_map(|i| [0, i, i + 1])
+        .collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Entities spawned for each chunk currently in view.
+#[derive(Default)]
+pub(crate) struct LoadedChunks(HashMap<(usize, usize), Vec<Entity>>);
+
+impl LoadedChunks {
+    /// Whether the cells around a world position, one in every direction, are spawned, so
+    /// anything there has the ground under it and the walls beside it to collide with.
+    pub(crate) fn around(
+        &self,
+        grid: &LevelGrid,
+        streaming: &LevelStreaming,
+        level: &Level,
+        position: Vec2,
+    ) -> bool {
+        let offsets = [-1.0, 0.0, 1.0];
+
+        offsets
+            .iter()
+            .flat_map(|&x| offsets.iter().map(move |&y| Vec2::new(x, y)))
+            .filter_map(|offset| grid.world_to_cell(position + offset * grid.tile_size))
+            .filter(|&(x, y)| level.get(x, y).is_some())
+            .all(|cell| self.0.contains_key(&streaming.chunk_of(cell)))
+    }
+}
+
 struct LevelLoader;
 
 impl LevelLoader {
@@ -88,36 +618,11 @@ impl LevelLoader {
         bytes: &'a [u8],
         load_context: &'a mut LoadContext<'_>,
     ) -> Result<(), anyhow::Error> {
-        let mut level = Level::default();
         let input_str = std::str::from_utf8(bytes)?;
 
         log::info!("Loading level from string");
 
-        for (j, row) in input_str.lines().enumerate() {
-            if j >= LEVEL_HEIGHT {
-                return Err(anyhow::format_err!("height larger than max {LEVEL_HEIGHT}"));
-            }
-
-            for (i, c) in row.chars().enumerate() {
-                if i >= LEVEL_WIDTH {
-                    return Err(anyhow::format_err!("width larger than max {LEVEL_WIDTH}"));
-                }
-
-                level.tiles[j][i] = match c {
-                    '[' => Tile::Floor(Side::Left),
-                    '=' => Tile::Floor(Side::Middle),
-                    ']' => Tile::Floor(Side::Right),
-                    '¬' => Tile::Floor(Side::TopRight),
-                    '4' => Tile::Floor(Side::TopLeft),
-                    '-' => Tile::Floor(Side::Top),
-                    'L' => Tile::Floor(Side::BotLeft),
-                    '_' => Tile::Floor(Side::Bot),
-                    '/' => Tile::Floor(Side::BotRight),
-                    '•' => Tile::Floor(Side::Standalone),
-                    _ => Tile::Empty,
-                };
-            }
-        }
+        let level: Level = input_str.parse()?;
 
         load_context.set_default_asset(LoadedAsset::new(level));
         Ok(())
@@ -139,37 +644,398 @@ impl AssetLoader for LevelLoader {
 }
 
 // TODO: maybe support hot-reloading the map, could be useful for iteration
-fn spawn_level(
+#[allow(clippy::too_many_arguments)]
+fn stream_chunks(
     mut commands: Commands,
     maps: Res<MapAssets>,
     level_assets: Res<Assets<Level>>,
     tiles: Res<TileAssets>,
-    windows: Res<Windows>,
+    slopes: Res<SlopeMeshes>,
+    grid: Res<LevelGrid>,
+    streaming: Res<LevelStreaming>,
+    mut loaded: ResMut<LoadedChunks>,
+    loaders: Query<(&Transform, &OrthographicProjection), With<ChunkLoader>>,
 ) {
-    let window = windows.primary();
-    let tile_start = Vec3::new(-window.width() / 2.0, window.height() / 2.0, 10.0);
-
     let level0 = level_assets.get(&maps.level0).unwrap();
 
-    for (j, row) in level0.tiles.iter().enumerate() {
-        for (i, &tile) in row.iter().enumerate() {
-            if let Tile::Floor(side) = tile {
-                let position = tile_start + Vec3::new(i as f32 * 48.0, j as f32 * -48.0, 0.0);
-
-                commands
-                    .spawn_bundle(SpriteSheetBundle {
-                        sprite: TextureAtlasSprite::new(side.index()),
-                        texture_atlas: tiles.tiles.clone(),
-                        transform: Transform::from_translation(position),
-                        ..Default::default()
-                    })
-                    .insert(tile)
-                    .insert(RigidBody::Static)
-                    .insert(CollisionShape::Cuboid {
-                        half_extends: Vec3::new(24.0, 24.0, 0.0),
-                        border_radius: None,
-                    });
+    let in_view: HashSet<_> = loaders
+        .iter()
+        .flat_map(|(transform, projection)| {
+            let center = transform.translation.truncate();
+            let view_min =
+                center + Vec2::new(projection.left, projection.bottom) * projection.scale;
+            let view_max = center + Vec2::new(projection.right, projection.top) * projection.scale;
+            streaming.chunks_in_view(&grid, level0, view_min, view_max)
+        })
+        .collect();
+
+    loaded.0.retain(|chunk, entities| {
+        if in_view.contains(chunk) {
+            return true;
+        }
+
+        for &entity in entities.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        false
+    });
+
+    for chunk in in_view {
+        loaded.0.entry(chunk).or_insert_with(|| {
+            streaming
+                .chunk_tiles(level0, chunk)
+                .filter_map(|(i, j, tile)| {
+                    spawn_tile(&mut commands, &tiles, &slopes, &grid, level0, (i, j), tile)
+                })
+                .collect()
+        });
+    }
+}
+
+fn spawn_tile(
+    commands: &mut Commands,
+    tiles: &TileAssets,
+    slopes: &SlopeMeshes,
+    grid: &LevelGrid,
+    level: &Level,
+    (i, j): (usize, usize),
+    tile: Tile,
+) -> Option<Entity> {
+    let position = grid.cell_to_world(i, j).extend(10.0);
+    let tile_size = Vec2::splat(grid.tile_size);
+    let half_extends = (tile_size / 2.0).extend(0.0);
+
+    let mut entity = match tile {
+        Tile::Empty | Tile::Crate => return None,
+        Tile::Floor(side) => {
+            let mut entity = commands.spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index: side.index(),
+                    custom_size: Some(tile_size),
+                    ..Default::default()
+                },
+                texture_atlas: tiles.tiles.clone(),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            });
+
+            entity
+                .insert(RigidBody::Static)
+                .insert(CollisionShape::Cuboid {
+                    half_extends,
+                    border_radius: None,
+                });
+            entity
+        }
+        Tile::Slope(slope) => {
+            let mut entity = commands.spawn_bundle(MaterialMesh2dBundle {
+                mesh: slopes.meshes[&slope].clone(),
+                material: slopes.material.clone(),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            });
+
+            entity
+                .insert(RigidBody::Static)
+                .insert(CollisionShape::ConvexHull {
+                    points: slope
+                        .outline()
+                        .into_iter()
+                        .map(|corner| (corner * tile_size).extend(0.0))
+                        .collect(),
+                    border_radius: None,
+                });
+            entity
+        }
+        Tile::Ladder => {
+            let mut entity = commands.spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index: LADDER_INDEX,
+                    custom_size: Some(tile_size),
+                    ..Default::default()
+                },
+                texture_atlas: tiles.tiles.clone(),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            });
+
+            entity
+                .insert(RigidBody::Sensor)
+                // narrower than the tile, so the player has to be roughly
+                // lined up with the ladder to grab it
+                .insert(CollisionShape::Cuboid {
+                    half_extends: half_extends * Vec3::new(0.5, 1.0, 0.0),
+                    border_radius: None,
+                });
+            entity
+        }
+        Tile::Liquid(liquid) => {
+            let mut entity = commands.spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: liquid.color(),
+                    custom_size: Some(tile_size),
+                    ..Default::default()
+                },
+                // drawn in front of the player, so they look submerged
+                transform: Transform::from_translation(position + Vec3::Z * 140.0),
+                ..Default::default()
+            });
+
+            entity
+                .insert(RigidBody::Sensor)
+                .insert(CollisionShape::Cuboid {
+                    half_extends,
+                    border_radius: None,
+                });
+
+            if liquid == Liquid::Acid {
+                entity.insert(Hazard);
+            }
+            entity
+        }
+        Tile::Vent => {
+            let mut entity = commands.spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index: Side::Standalone.index(),
+                    custom_size: Some(tile_size),
+                    ..Default::default()
+                },
+                texture_atlas: tiles.tiles.clone(),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            });
+
+            entity
+                .insert(RigidBody::Static)
+                .insert(CollisionShape::Cuboid {
+                    half_extends,
+                    border_radius: None,
+                });
+
+            let reach = (1..=VENT_REACH)
+                .take_while(|&dy| {
+                    j.checked_sub(dy)
+                        .and_then(|y| level.get(i, y))
+                        .is_some_and(|tile| !tile.is_solid())
+                })
+                .count();
+
+            if reach > 0 {
+                let height = reach as f32 * grid.tile_size;
+
+                // a child, so it comes and goes with the vent, but with its own body
+                // as heron only tracks one collision per pair of bodies
+                entity.with_children(|parent| {
+                    parent
+                        .spawn_bundle(SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::rgba(1.0, 1.0, 1.0, 0.1),
+                                custom_size: Some(Vec2::new(tile_size.x, height)),
+                                ..Default::default()
+                            },
+                            transform: Transform::from_xyz(
+                                0.0,
+                                (height + grid.tile_size) / 2.0,
+                                1.0,
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(Updraft::default())
+                        .insert(RigidBody::Sensor)
+                        .insert(CollisionShape::Cuboid {
+                            half_extends: Vec3::new(half_extends.x * 0.8, height / 2.0, 0.0),
+                            border_radius: None,
+                        });
+                });
+            }
+            entity
+        }
+        Tile::Checkpoint => {
+            let mut entity = commands.spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: CHECKPOINT_COLOR,
+                    custom_size: Some(tile_size * Vec2::new(0.2, 1.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            });
+
+            entity
+                .insert(RigidBody::Sensor)
+                .insert(CollisionShape::Cuboid {
+                    half_extends,
+                    border_radius: None,
+                });
+            entity
+        }
+        Tile::PressurePlate => {
+            let size = tile_size * Vec2::new(0.8, 0.15);
+            let mut entity = commands.spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: PLATE_COLOR,
+                    custom_size: Some(size),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(
+                    position - Vec3::Y * (grid.tile_size - size.y) / 2.0,
+                ),
+                ..Default::default()
+            });
+
+            entity
+                .insert(PressurePlate { cell: (i, j) })
+                .insert(RigidBody::Sensor)
+                .insert(CollisionShape::Cuboid {
+                    half_extends: (size / 2.0).extend(0.0),
+                    border_radius: None,
+                })
+                .insert(Collisions::default());
+            entity
+        }
+    };
+
+    Some(entity.insert(tile).id())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    fn all_tiles() -> Vec<Tile> {
+        use Side::*;
+
+        let sides = [
+            TopLeft, Top, TopRight, BotLeft, Bot, BotRight, Left, Middle, Right, Standalone,
+        ];
+        let mut tiles = vec![
+            Tile::Empty,
+            Tile::Ladder,
+            Tile::Liquid(Liquid::Water),
+            Tile::Liquid(Liquid::Acid),
+            Tile::Vent,
+            Tile::Checkpoint,
+            Tile::Crate,
+            Tile::PressurePlate,
+        ];
+        tiles.extend(sides.map(Tile::Floor));
+        tiles.extend(Slope::ALL.map(Tile::Slope));
+        tiles
+    }
+
+    #[test]
+    fn every_tile_round_trips_through_its_glyph() {
+        let tiles = all_tiles();
+
+        for &tile in &tiles {
+            assert_eq!(Tile::from_glyph(tile.glyph()), tile, "{tile:?}");
+        }
+
+        let glyphs: HashSet<_> = tiles.iter().map(|tile| tile.glyph()).collect();
+        assert_eq!(glyphs.len(), tiles.len(), "two tiles share a glyph");
+
+        // and nothing else parses to anything but empty space
+        for c in (0..0x3000).filter_map(char::from_u32) {
+            let tile = Tile::from_glyph(c);
+            assert!(
+                tile == Tile::Empty || tile.glyph() == c,
+                "{c:?} parses to {tile:?}, written as {:?}",
+                tile.glyph()
+            );
+        }
+    }
+
+    #[test]
+    fn generated_level_round_trips() {
+        let tiles = all_tiles();
+        let mut rng = StdRng::seed_from_u64(28);
+
+        for _ in 0..20 {
+            let (width, height) = (rng.gen_range(1..40), rng.gen_range(1..20));
+            let mut level = Level::new(width, height);
+            for y in 0..height {
+                // leave some rows and the last column empty, which must still be kept
+                if rng.gen_bool(0.2) {
+                    continue;
+                }
+                for x in 0..width - 1 {
+                    level.set(x, y, tiles[rng.gen_range(0..tiles.len())]);
+                }
+            }
+
+            let written = level.to_string();
+            let parsed: Level = written.parse().unwrap();
+            assert_eq!(parsed, level, "written as:\n{written}");
+            assert_eq!(parsed.to_string(), written);
+        }
+    }
+
+    #[test]
+    fn shallow_ramps_rise_at_22_5_degrees() {
+        let ramp = [
+            Slope::ShallowRightLow,
+            Slope::ShallowRightMid,
+            Slope::ShallowRightHigh,
+        ];
+        // the top edges of each tile, laid out side by side with the ramp starting at 0, 0
+        let top: Vec<_> = ramp
+            .iter()
+            .enumerate()
+            .flat_map(|(i, slope)| {
+                slope
+                    .outline()
+                    .into_iter()
+                    .map(move |corner| corner + Vec2::new(i as f32 + 0.5, 0.5))
+            })
+            .filter(|corner| corner.y > 0.0)
+            .collect();
+
+        for corner in top {
+            let expected = (corner.x * 22.5f32.to_radians().tan()).min(1.0);
+            assert!(
+                (corner.y - expected).abs() < 1e-5,
+                "{corner} is off the ramp"
+            );
+        }
+    }
+
+    #[test]
+    fn slope_outlines_are_counter_clockwise() {
+        for slope in Slope::ALL {
+            let outline = slope.outline();
+            for (i, &corner) in outline.iter().enumerate() {
+                let next = outline[(i + 1) % outline.len()];
+                let after = outline[(i + 2) % outline.len()];
+                assert!(
+                    (next - corner).perp_dot(after - next) > 0.0,
+                    "{slope:?} at {next}"
+                );
             }
         }
     }
+
+    #[test]
+    fn only_surroundings_inside_the_level_must_be_loaded() {
+        let (grid, streaming) = (LevelGrid::default(), LevelStreaming::default());
+        let level = Level::new(16, 16);
+        let mut loaded = LoadedChunks::default();
+        loaded.0.insert((0, 0), Vec::new());
+
+        let around = |x, y| loaded.around(&grid, &streaming, &level, grid.cell_to_world(x, y));
+        assert!(around(3, 3));
+        assert!(around(0, 0), "past the edge of the level");
+        assert!(!around(7, 3), "next to the unloaded chunk on the right");
+        assert!(!around(3, 7), "above the unloaded chunk below");
+    }
+
+    #[test]
+    fn level_without_rows_loses_its_width() {
+        let level = Level::new(5, 0);
+
+        assert_eq!(level.to_string(), "");
+        assert_eq!("".parse::<Level>().unwrap(), Level::new(0, 0));
+    }
 }
@@ -1,16 +1,39 @@
-mod actions;
+mod abilities;
+pub mod actions;
+mod animation;
 mod audio;
-mod level;
+mod character;
+mod ground;
+mod health;
+mod kinematic;
+pub mod level;
 mod loading;
 mod menu;
+mod movement;
 mod player;
+mod props;
+pub mod replay;
+mod settings;
+mod timestep;
+mod touch_controls;
 
+use abilities::AbilitiesPlugin;
 use actions::ActionsPlugin;
+use animation::AnimationPlugin;
 use audio::InternalAudioPlugin;
+use character::CharacterPlugin;
+use ground::GroundPlugin;
+use health::HealthPlugin;
+use kinematic::KinematicPlugin;
 use level::LevelPlugin;
 use loading::LoadingPlugin;
 use menu::MenuPlugin;
 use player::PlayerPlugin;
+use props::PropsPlugin;
+use replay::ReplayPlugin;
+use settings::SettingsPlugin;
+use timestep::TimestepPlugin;
+use touch_controls::TouchControlsPlugin;
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
@@ -29,6 +52,8 @@ enum GameState {
     Playing,
     /// Here the menu is drawn and waiting for player interaction
     Menu,
+    /// Rebinding controls, reached from the menu
+    Settings,
 }
 
 pub struct GamePlugin;
@@ -38,16 +63,28 @@ impl Plugin for GamePlugin {
         app.add_state(GameState::Loading)
             .add_plugin(LoadingPlugin)
             .add_plugin(MenuPlugin)
+            .add_plugin(SettingsPlugin)
             .add_plugin(ActionsPlugin)
+            .add_plugin(TouchControlsPlugin)
+            .add_plugin(ReplayPlugin)
             .add_plugin(InternalAudioPlugin)
             .add_plugin(LevelPlugin)
             .add_plugin(PlayerPlugin)
+            .add_plugin(AbilitiesPlugin)
+            .add_plugin(CharacterPlugin)
+            .add_plugin(AnimationPlugin)
+            .add_plugin(HealthPlugin)
+            .add_plugin(GroundPlugin)
+            .add_plugin(KinematicPlugin)
+            .add_plugin(PropsPlugin)
             .insert_resource(Gravity::from(Vec2::new(0.0, -250.0)))
-            .add_plugin(PhysicsPlugin::default());
+            .add_plugin(PhysicsPlugin::default())
+            // after everything running in Update, which it moves into ticks with a fixed timestep
+            .add_plugin(TimestepPlugin);
 
         #[cfg(debug_assertions)]
         {
-            app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+            app.add_plugin(FrameTimeDiagnosticsPlugin)
                 .add_plugin(LogDiagnosticsPlugin::default());
         }
     }
@@ -2,6 +2,8 @@ use bevy::prelude::*;
 use bevy_asset_loader::{AssetCollection, AssetLoader};
 use bevy_kira_audio::AudioSource;
 
+use crate::animation::AnimationSet;
+use crate::character::CharacterDefinition;
 use crate::level::Level;
 use crate::GameState;
 
@@ -16,6 +18,8 @@ impl Plugin for LoadingPlugin {
             .with_collection::<FontAssets>()
             .with_collection::<AudioAssets>()
             .with_collection::<SpriteAssets>()
+            .with_collection::<AnimationAssets>()
+            .with_collection::<CharacterAssets>()
             .with_collection::<TileAssets>()
             .with_collection::<MapAssets>()
             .continue_to_state(GameState::Menu)
@@ -43,6 +47,24 @@ pub struct SpriteAssets {
     #[asset(path = "sprites/dogken.png")]
     #[asset(texture_atlas(tile_size_x = 46.0, tile_size_y = 34.0, columns = 1, rows = 10))]
     pub dogken: Handle<TextureAtlas>,
+    // TODO: placeholder until the catfish has its own sprite sheet
+    #[asset(path = "sprites/dogken.png")]
+    #[asset(texture_atlas(tile_size_x = 46.0, tile_size_y = 34.0, columns = 1, rows = 10))]
+    pub catfish: Handle<TextureAtlas>,
+}
+
+#[derive(AssetCollection)]
+pub struct AnimationAssets {
+    #[asset(path = "animations/dogken.anim.ron")]
+    pub dogken: Handle<AnimationSet>,
+}
+
+#[derive(AssetCollection)]
+pub struct CharacterAssets {
+    #[asset(path = "characters/dogken.character.ron")]
+    pub dogken: Handle<CharacterDefinition>,
+    #[asset(path = "characters/catfish.character.ron")]
+    pub catfish: Handle<CharacterDefinition>,
 }
 
 #[derive(AssetCollection)]
@@ -1,9 +1,11 @@
 // disable console on windows for release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use bevy::asset::AssetServerSettings;
 use bevy::prelude::{App, ClearColor, Color, Msaa, WindowDescriptor};
 use bevy::DefaultPlugins;
 
+use dr_fingers::replay::ReplayMode;
 use dr_fingers::GamePlugin;
 
 fn main() {
@@ -17,6 +19,13 @@ fn main() {
             title: "Dr. Fingers".to_string(),
             ..Default::default()
         })
+        // reload assets like character definitions as soon as they change on disk
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: cfg!(feature = "dev"),
+            ..Default::default()
+        })
+        // `--record <file>` or `--replay <file>`
+        .insert_resource(ReplayMode::from_args(std::env::args().skip(1)))
         .add_plugins(DefaultPlugins)
         .add_plugin(GamePlugin)
         .run();
@@ -5,17 +5,22 @@ use crate::GameState;
 
 pub struct MenuPlugin;
 
-/// This plugin is responsible for the game menu (containing only one button...)
+/// This plugin is responsible for the game menu, with buttons to play or change the controls
 /// The menu is only drawn during the State `GameState::Menu` and is removed when that state is exited
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ButtonColors>()
+            .add_startup_system(spawn_ui_camera)
+            .add_system(highlight_buttons)
             .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(setup_menu))
-            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(click_play_button));
+            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(click_menu_buttons))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Menu).with_system(despawn_screen::<MenuScreen>),
+            );
     }
 }
 
-struct ButtonColors {
+pub(crate) struct ButtonColors {
     normal: UiColor,
     hovered: UiColor,
 }
@@ -29,17 +34,49 @@ impl Default for ButtonColors {
     }
 }
 
-fn setup_menu(
-    mut commands: Commands,
-    font_assets: Res<FontAssets>,
-    button_colors: Res<ButtonColors>,
-) {
+#[derive(Component)]
+struct MenuScreen;
+
+#[derive(Component)]
+enum MenuButton {
+    Play,
+    Controls,
+}
+
+fn spawn_ui_camera(mut commands: Commands) {
     commands.spawn_bundle(UiCameraBundle::default());
-    commands
+}
+
+/// A full-screen node stacking its children from the top down, in the middle of the screen.
+pub(crate) fn screen_bundle() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            // UI is laid out bottom up, so reverse it
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    }
+}
+
+/// A button of the given width with a line of text on it.
+pub(crate) fn spawn_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    width: f32,
+    label: &str,
+    marker: impl Component,
+) {
+    parent
         .spawn_bundle(ButtonBundle {
             style: Style {
-                size: Size::new(Val::Px(120.0), Val::Px(50.0)),
-                margin: Rect::all(Val::Auto),
+                size: Size::new(Val::Px(width), Val::Px(50.0)),
+                margin: Rect::all(Val::Px(5.0)),
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 ..Default::default()
@@ -47,44 +84,118 @@ fn setup_menu(
             color: button_colors.normal,
             ..Default::default()
         })
+        .insert(marker)
         .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text {
-                    sections: vec![TextSection {
-                        value: "Play".to_string(),
-                        style: TextStyle {
-                            font: font_assets.fira_sans.clone(),
-                            font_size: 40.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
-                        },
-                    }],
-                    alignment: Default::default(),
-                },
+            parent.spawn_bundle(button_text(font_assets, label, 40.0));
+        });
+}
+
+/// A smaller button, as wide as its text, to fit several on a line.
+pub(crate) fn spawn_small_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    label: &str,
+    marker: impl Component,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Auto, Val::Px(32.0)),
+                margin: Rect::all(Val::Px(3.0)),
+                padding: Rect::all(Val::Px(6.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
                 ..Default::default()
-            });
+            },
+            color: button_colors.normal,
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            parent.spawn_bundle(button_text(font_assets, label, 20.0));
         });
 }
 
-type InteractedButton = (Changed<Interaction>, With<Button>);
+pub(crate) fn button_text(font_assets: &FontAssets, label: &str, font_size: f32) -> TextBundle {
+    TextBundle {
+        text: Text {
+            sections: vec![TextSection {
+                value: label.to_string(),
+                style: TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            }],
+            alignment: Default::default(),
+        },
+        ..Default::default()
+    }
+}
 
-fn click_play_button(
+fn setup_menu(
     mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+) {
+    commands
+        .spawn_bundle(screen_bundle())
+        .insert(MenuScreen)
+        .with_children(|parent| {
+            spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                200.0,
+                "Play",
+                MenuButton::Play,
+            );
+            spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                200.0,
+                "Controls",
+                MenuButton::Controls,
+            );
+        });
+}
+
+/// Despawns everything under the root node of a screen.
+pub(crate) fn despawn_screen<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+type InteractedButton = (Changed<Interaction>, With<Button>);
+
+fn highlight_buttons(
     button_colors: Res<ButtonColors>,
+    mut interaction_query: Query<(&Interaction, &mut UiColor), InteractedButton>,
+) {
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        *color = match *interaction {
+            Interaction::Hovered => button_colors.hovered,
+            _ => button_colors.normal,
+        };
+    }
+}
+
+fn click_menu_buttons(
     mut state: ResMut<State<GameState>>,
-    mut interaction_query: Query<(Entity, &Interaction, &mut UiColor), InteractedButton>,
+    interaction_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
 ) {
-    for (button, interaction, mut color) in interaction_query.iter_mut() {
-        match *interaction {
-            Interaction::Clicked => {
-                commands.entity(button).despawn_recursive();
-                state.set(GameState::Playing).unwrap();
-            }
-            Interaction::Hovered => {
-                *color = button_colors.hovered;
-            }
-            Interaction::None => {
-                *color = button_colors.normal;
-            }
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
         }
+
+        let next = match button {
+            MenuButton::Play => GameState::Playing,
+            MenuButton::Controls => GameState::Settings,
+        };
+        state.set(next).unwrap();
     }
 }
@@ -0,0 +1,102 @@
+//! Frame-rate independent integration of running, kept free of any ECS types so it can be
+//! stepped on its own, e.g. to compare trajectories at different frame rates.
+
+/// How a character speeds up and slows down while running, in units per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Running {
+    /// Horizontal velocity gained per second while pushing in a direction.
+    pub accel: f32,
+    /// Horizontal velocity lost per second, whether pushing or not.
+    pub damping: f32,
+    pub max_speed: f32,
+}
+
+impl Running {
+    /// Velocity gained over `delta` seconds from `input`, with `control` scaling the
+    /// acceleration (e.g. less of it in the air).
+    pub fn push(&self, input: Option<f32>, control: f32, delta: f32) -> f32 {
+        input.unwrap_or(0.0) * self.accel * control * delta
+    }
+
+    /// Damps and clamps a velocity over `delta` seconds. Damping brings it to a standstill,
+    /// but never past it.
+    pub fn settle(&self, velocity: f32, delta: f32) -> f32 {
+        let damped = velocity - velocity.signum() * (self.damping * delta).min(velocity.abs());
+        damped.clamp(-self.max_speed, self.max_speed)
+    }
+
+    /// New horizontal velocity after running for `delta` seconds.
+    pub fn step(&self, velocity: f32, input: Option<f32>, control: f32, delta: f32) -> f32 {
+        self.settle(velocity + self.push(input, control, delta), delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default character's running, see `assets/characters/dogken.character.ron`.
+    const RUNNING: Running = Running {
+        accel: 1800.0,
+        damping: 120.0,
+        max_speed: 175.0,
+    };
+
+    /// Run right for a second, let go, turn around, then let go again.
+    fn input_at(time: f32) -> Option<f32> {
+        match time {
+            t if t < 1.0 => Some(1.0),
+            t if t < 1.5 => None,
+            t if t < 2.0 => Some(-1.0),
+            _ => None,
+        }
+    }
+
+    /// Position and velocity every half second over three seconds, stepping `rate` times a
+    /// second. The rate must step exactly onto each sample and each change of input.
+    fn simulate(rate: u32) -> Vec<(f32, f32)> {
+        let delta = 1.0 / rate as f32;
+        let (mut position, mut velocity) = (0.0, 0.0);
+        let mut samples = vec![(position, velocity)];
+
+        for step in 0..rate * 3 {
+            velocity = RUNNING.step(velocity, input_at(step as f32 * delta), 1.0, delta);
+            position += velocity * delta;
+
+            if (step + 1) % (rate / 2) == 0 {
+                samples.push((position, velocity));
+            }
+        }
+
+        samples
+    }
+
+    #[test]
+    fn trajectory_is_the_same_at_any_frame_rate() {
+        let reference = simulate(960);
+
+        for rate in [30, 60, 120, 144, 240] {
+            let trajectory = simulate(rate);
+            assert_eq!(trajectory.len(), reference.len());
+
+            for (i, (&(p, v), &(ref_p, ref_v))) in trajectory.iter().zip(&reference).enumerate() {
+                let time = i as f32 / 2.0;
+                assert!(
+                    (p - ref_p).abs() < 4.0,
+                    "{rate} Hz at {time}s: at {p}, not {ref_p}"
+                );
+                assert!(
+                    (v - ref_v).abs() < 0.5,
+                    "{rate} Hz at {time}s: moving {v}, not {ref_v}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn damping_stops_without_reversing() {
+        let velocity = RUNNING.settle(1.0, 0.1);
+        assert_eq!(velocity, 0.0);
+        assert_eq!(RUNNING.settle(-1.0, 0.1), 0.0);
+    }
+}
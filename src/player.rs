@@ -1,11 +1,18 @@
-use std::f32::consts;
-
+use bevy::math::{const_vec2, const_vec3};
 use bevy::prelude::*;
 use heron::prelude::*;
+use serde::Deserialize;
 
-use crate::actions::Actions;
-use crate::level;
-use crate::loading::SpriteAssets;
+use crate::abilities::{AbilityEvent, Dash, Glide, WallJump};
+use crate::actions::{Actions, ActionsSystem};
+use crate::animation::{AnimationSystem, Animator, Facing};
+use crate::character::{CharacterDefinition, Roster};
+use crate::ground::{GroundProbe, Grounded};
+use crate::health::{DamageEvent, Dying, Health, HealthSystem, Respawn};
+use crate::level::{self, Level, LevelGrid};
+use crate::loading::{AnimationAssets, CharacterAssets, MapAssets, SpriteAssets};
+use crate::movement::Running;
+use crate::timestep::GameTime;
 use crate::GameState;
 
 pub struct PlayerPlugin;
@@ -16,37 +23,220 @@ pub struct Player;
 #[derive(Component)]
 pub struct PlayerCollider;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum PlayerSystem {
+    Movement,
+    Animation,
+}
+
+/// How fast a character runs and how high it jumps.
+#[derive(Component, Clone, Copy, Debug, Deserialize)]
+pub struct MovementStats {
+    /// Horizontal velocity gained per second while running.
+    pub move_accel: f32,
+    pub max_speed_x: f32,
+    pub jump_velocity: f32,
+    /// Horizontal velocity lost per second, whether running or not.
+    pub damping: f32,
+}
+
+impl MovementStats {
+    pub fn running(&self) -> Running {
+        Running {
+            accel: self.move_accel,
+            damping: self.damping,
+            max_speed: self.max_speed_x,
+        }
+    }
+}
+
+/// How the player is currently moving through the level.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MovementMode {
+    /// Running and jumping, affected by gravity.
+    #[default]
+    Normal,
+    /// Holding on to a ladder, with gravity suspended.
+    Climbing,
+    /// Submerged in a liquid, see [`SwimPhysics`].
+    Swimming,
+}
+
+/// Tuning for movement inside liquids, independent of the global [`Gravity`].
+pub struct SwimPhysics {
+    /// Fraction of the global gravity that still applies while submerged.
+    pub gravity_scale: f32,
+    /// Upward acceleration pushing the player towards the surface.
+    pub buoyancy: f32,
+    /// Fraction of the player's velocity lost per second.
+    pub drag: f32,
+    /// Upward velocity of a single swim stroke.
+    pub stroke_velocity: f32,
+    /// Seconds between swim strokes while holding jump.
+    pub stroke_cooldown: f32,
+}
+
+impl Default for SwimPhysics {
+    fn default() -> Self {
+        Self {
+            gravity_scale: 0.4,
+            buoyancy: 60.0,
+            drag: 2.5,
+            stroke_velocity: 180.0,
+            stroke_cooldown: 0.4,
+        }
+    }
+}
+
+#[derive(Component)]
+struct SwimStroke(Timer);
+
+/// Tuning for how forgiving and responsive jumping is.
+pub struct JumpPhysics {
+    /// Seconds after running off a ledge during which the player can still jump.
+    pub coyote_time: f32,
+    /// Seconds before landing during which a jump press is remembered.
+    pub buffer_time: f32,
+    /// Fraction of the upward velocity kept when jump is released early.
+    pub release_velocity_scale: f32,
+}
+
+impl Default for JumpPhysics {
+    fn default() -> Self {
+        Self {
+            coyote_time: 0.1,
+            buffer_time: 0.12,
+            release_velocity_scale: 0.4,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub(crate) struct JumpState {
+    /// Seconds since the player was last standing on the floor.
+    since_floor: f32,
+    /// Seconds since jump was pressed, until it is used or forgotten.
+    pub(crate) buffered: Option<f32>,
+    /// Whether the player is rising from a jump, which can still be cut short.
+    pub(crate) rising: bool,
+    /// Which side a touched wall is on: -1.0 for left, 1.0 for right.
+    pub(crate) wall_side: Option<f32>,
+}
+
+impl JumpState {
+    pub(crate) fn on_floor(&self) -> bool {
+        self.since_floor == 0.0
+    }
+}
+
 /// This plugin handles player related stuff like movement
 /// Player logic is only active during the State `GameState::Playing`
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_enter(GameState::Playing)
-                .with_system(spawn_player)
-                .with_system(spawn_camera),
-        )
-        .add_system_set(
-            SystemSet::on_update(GameState::Playing)
-                .with_system(move_player)
-                .with_system(animate_player),
-        );
+        app.init_resource::<SwimPhysics>()
+            .init_resource::<JumpPhysics>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(spawn_player)
+                    .with_system(spawn_camera),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        move_player
+                            .label(PlayerSystem::Movement)
+                            .after(ActionsSystem::Read),
+                    )
+                    .with_system(follow_player)
+                    .with_system(touch_hazards.before(HealthSystem::Damage))
+                    .with_system(touch_checkpoints)
+                    .with_system(
+                        animate_player
+                            .label(PlayerSystem::Animation)
+                            .after(PlayerSystem::Movement)
+                            .before(AnimationSystem::Play),
+                    ),
+            );
     }
 }
 
+#[derive(Component)]
+struct PlayerCamera;
+
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(PlayerCamera)
+        .insert(level::ChunkLoader);
 }
 
-fn spawn_player(mut commands: Commands, sprites: Res<SpriteAssets>) {
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            sprite: TextureAtlasSprite::new(0),
-            texture_atlas: sprites.dogken.clone(),
-            transform: Transform::from_xyz(0.0, 0.0, 100.0),
-            ..Default::default()
-        })
+/// Keeps the camera centred on the player, without showing anything outside the level.
+fn follow_player(
+    maps: Res<MapAssets>,
+    level_assets: Res<Assets<Level>>,
+    grid: Res<LevelGrid>,
+    windows: Res<Windows>,
+    player_query: Query<&Transform, (With<Player>, Without<PlayerCamera>)>,
+    mut camera_query: Query<&mut Transform, With<PlayerCamera>>,
+) {
+    let level = match level_assets.get(&maps.level0) {
+        Some(level) => level,
+        None => return,
+    };
+
+    let window = windows.primary();
+    let half_view = Vec2::new(window.width(), window.height()) / 2.0;
+    let (min, max) = grid.level_bounds(level);
+
+    let clamp_axis = |target: f32, min: f32, max: f32, half_view: f32| {
+        if max - min < half_view * 2.0 {
+            (min + max) / 2.0
+        } else {
+            target.clamp(min + half_view, max - half_view)
+        }
+    };
+
+    for player_transform in player_query.iter() {
+        for mut camera_transform in camera_query.iter_mut() {
+            let target = player_transform.translation;
+            camera_transform.translation.x = clamp_axis(target.x, min.x, max.x, half_view.x);
+            camera_transform.translation.y = clamp_axis(target.y, min.y, max.y, half_view.y);
+        }
+    }
+}
+
+const PLAYER_SPAWN: Vec3 = const_vec3!([0.0, 0.0, 100.0]);
+const PLAYER_HEALTH: u32 = 3;
+const HAZARD_KNOCKBACK: Vec2 = const_vec2!([120.0, 150.0]);
+
+fn spawn_player(
+    mut commands: Commands,
+    sprites: Res<SpriteAssets>,
+    animations: Res<AnimationAssets>,
+    characters: Res<CharacterAssets>,
+    definitions: Res<Assets<CharacterDefinition>>,
+    roster: Res<Roster>,
+    swim: Res<SwimPhysics>,
+) {
+    let character = roster.active();
+    let definition = definitions
+        .get(character.definition(&characters))
+        .expect("character definition not loaded");
+
+    let mut player = commands.spawn_bundle(SpriteSheetBundle {
+        sprite: TextureAtlasSprite {
+            color: character.color(),
+            ..TextureAtlasSprite::new(0)
+        },
+        texture_atlas: character.atlas(&sprites),
+        transform: Transform::from_translation(PLAYER_SPAWN),
+        ..Default::default()
+    });
+
+    player
         .insert(Player)
-        .insert(RigidBody::Dynamic)
+        .insert(character)
+        .insert(definition.movement)
         .insert(Collisions::default())
         // .insert(PhysicMaterial {
         //     restitution: 0.2,
@@ -56,99 +246,329 @@ fn spawn_player(mut commands: Commands, sprites: Res<SpriteAssets>) {
         // })
         .insert(RotationConstraints::lock())
         .insert(Velocity::default())
+        .insert(Acceleration::default())
+        .insert(MovementMode::default())
+        .insert(SwimStroke(Timer::from_seconds(swim.stroke_cooldown, false)))
+        .insert(JumpState::default())
+        .insert(GroundProbe::default())
+        .insert(Health::new(PLAYER_HEALTH))
+        .insert(Respawn {
+            point: PLAYER_SPAWN,
+        })
         .with_children(|commands| {
             commands
                 .spawn()
                 .insert(PlayerCollider)
-                // TODO: maybe trapezoidal convex hull instead?
-                .insert(CollisionShape::Capsule {
-                    half_segment: 14.0,
-                    radius: 16.0,
-                })
+                .insert(definition.collider())
                 .insert_bundle(TransformBundle::from_transform(
-                    Transform::from_translation(Vec3::Y * 2.5)
-                        .with_rotation(Quat::from_rotation_z(consts::PI / 2.0))
-                        .with_scale(Vec3::splat(10.0)),
+                    character.collider_transform(),
                 ));
         })
-        .insert(AnimationTimer(Timer::from_seconds(0.1, true)));
-}
+        .insert(character.animations(&animations))
+        .insert(Animator::new("idle"))
+        .insert(Facing::default());
 
-#[derive(Component)]
-struct AnimationTimer(Timer);
+    definition.give_controller(&mut player);
+    character.give_abilities(&mut player);
+}
 
+/// Picks the animation clip for the player's current state.
 fn animate_player(
-    time: Res<Time>,
     actions: Res<Actions>,
-    atlases: Res<Assets<TextureAtlas>>,
-    mut query: Query<
-        (
-            &mut AnimationTimer,
-            &mut TextureAtlasSprite,
-            &Handle<TextureAtlas>,
-        ),
-        With<Player>,
-    >,
+    mut ability_events: EventReader<AbilityEvent>,
+    mut query: Query<PlayerAnimation, With<Player>>,
 ) {
-    for (mut timer, mut sprite, atlas_handle) in query.iter_mut() {
-        timer.0.tick(time.delta());
-        if timer.0.finished() {
-            if let Some(movement) = actions.player_x_movement {
-                let atlas = atlases
-                    .get(atlas_handle)
-                    .expect("atlas for texture not found");
-
-                if movement > 0.0 {
-                    sprite.index = (sprite.index + 1) % atlas.textures.len();
-                } else if movement < 0.0 {
-                    sprite.index = sprite
-                        .index
-                        .checked_sub(1)
-                        .unwrap_or(atlas.textures.len() - 1);
+    let double_jumped = ability_events
+        .iter()
+        .any(|event| matches!(event, AbilityEvent::DoubleJump));
+
+    for (mut animator, mut facing, dying, mode, jump, velocity, glide, wall_jump, dash) in
+        query.iter_mut()
+    {
+        if dying.is_some() {
+            animator.play("die");
+            continue;
+        }
+
+        if let Some(towards) = actions.player_x_movement.and_then(Facing::from_movement) {
+            Facing::turn(&mut facing, towards);
+        }
+        animator.paused = false;
+
+        let airborne = matches!(
+            animator.clip(),
+            "jump" | "fall" | "glide" | "double_jump" | "wall_slide"
+        );
+        let playing_out = |clip| animator.clip() == clip && !animator.is_finished();
+
+        let clip = match *mode {
+            MovementMode::Climbing => {
+                // hold still while hanging on to the ladder
+                animator.paused = actions.player_y_movement.is_none();
+                "climb"
+            }
+            MovementMode::Swimming => "swim",
+            MovementMode::Normal if dash.is_some_and(Dash::is_dashing) => "dash",
+            MovementMode::Normal if jump.on_floor() => {
+                if airborne {
+                    "land"
+                } else if playing_out("land") {
+                    continue;
+                } else if actions.player_x_movement.is_some() {
+                    "run"
+                } else {
+                    "idle"
                 }
             }
-        }
+            MovementMode::Normal => {
+                if double_jumped || playing_out("double_jump") {
+                    "double_jump"
+                } else if wall_jump.is_some_and(WallJump::is_sliding) {
+                    "wall_slide"
+                } else if glide.is_some_and(Glide::is_gliding) {
+                    "glide"
+                } else if velocity.linear.y > 0.0 {
+                    "jump"
+                } else {
+                    "fall"
+                }
+            }
+        };
+
+        animator.play(clip);
     }
 }
 
-const MOVE_ACCEL: f32 = 30.0;
-const MAX_SPEED_X: f32 = 175.0;
-const JUMP_VELOCITY: f32 = 200.0;
+const CLIMB_SPEED: f32 = 100.0;
+/// Fastest the player can be moving up while still standing on the ground.
+const LANDING_SPEED: f32 = 10.0;
+
+type PlayerAnimation = (
+    &'static mut Animator,
+    &'static mut Facing,
+    Option<&'static Dying>,
+    &'static MovementMode,
+    &'static JumpState,
+    &'static Velocity,
+    Option<&'static Glide>,
+    Option<&'static WallJump>,
+    Option<&'static Dash>,
+);
+
+type PlayerMovement = (
+    &'static mut Velocity,
+    &'static mut Acceleration,
+    &'static mut MovementMode,
+    &'static mut SwimStroke,
+    &'static mut JumpState,
+    &'static MovementStats,
+    &'static Collisions,
+    Option<&'static Grounded>,
+);
 
 fn move_player(
+    time: Res<GameTime>,
     actions: Res<Actions>,
-    mut player_query: Query<(&mut Velocity, &Collisions), With<Player>>,
+    gravity: Res<Gravity>,
+    swim: Res<SwimPhysics>,
+    jump_physics: Res<JumpPhysics>,
+    mut player_query: Query<PlayerMovement, (With<Player>, Without<Dying>)>,
     tiles_query: Query<&level::Tile>,
 ) {
-    for (mut player_vel, collisions) in player_query.iter_mut() {
-        let mut on_floor = false;
+    let delta = time.delta_seconds();
+
+    for (
+        mut player_vel,
+        mut player_accel,
+        mut mode,
+        mut stroke,
+        mut jump,
+        stats,
+        collisions,
+        grounded,
+    ) in player_query.iter_mut()
+    {
+        let running = stats.running();
+
+        // the probe still finds the ground for a moment after jumping off it
+        // (measured off the ground, since running up a slope moves upwards too)
+        let on_floor = grounded.is_some_and(|grounded| {
+            player_vel.linear.truncate().dot(grounded.normal) <= LANDING_SPEED
+        });
+        let mut on_ladder = false;
+        let mut in_liquid = false;
+        jump.wall_side = None;
 
         for collision in collisions.collision_data() {
-            if tiles_query.contains(collision.rigid_body_entity()) {
-                for normal in collision.normals() {
-                    if normal.y < -0.9 {
-                        on_floor = true;
-                        break;
+            match tiles_query.get(collision.rigid_body_entity()) {
+                Ok(tile) if tile.is_solid() => {
+                    // normals point from the player towards the tile
+                    for normal in collision.normals() {
+                        if normal.x.abs() > 0.9 {
+                            jump.wall_side = Some(normal.x.signum());
+                        }
                     }
                 }
+                Ok(level::Tile::Ladder) => on_ladder = true,
+                Ok(level::Tile::Liquid(_)) => in_liquid = true,
+                _ => {}
+            }
+        }
+
+        // climbing down into the floor just means letting go of the ladder
+        let climb_input = actions
+            .player_y_movement
+            .is_some_and(|movement| movement > 0.0 || !on_floor);
+
+        *mode = match *mode {
+            _ if in_liquid => MovementMode::Swimming,
+            MovementMode::Swimming => MovementMode::Normal,
+            MovementMode::Normal if on_ladder && climb_input => MovementMode::Climbing,
+            MovementMode::Climbing
+                if !on_ladder
+                    || actions.player_jump_started
+                    || (on_floor && actions.player_y_movement.is_some() && !climb_input) =>
+            {
+                if actions.player_jump_started {
+                    player_vel.linear.y = stats.jump_velocity;
+                    jump.rising = true;
+                }
+                MovementMode::Normal
+            }
+            mode => mode,
+        };
+
+        if *mode == MovementMode::Climbing {
+            // cancel out gravity entirely while holding on to the ladder
+            player_accel.linear = -gravity.vector();
+            player_vel.linear.x = actions.player_x_movement.unwrap_or(0.0) * CLIMB_SPEED;
+            player_vel.linear.y = actions.player_y_movement.unwrap_or(0.0) * CLIMB_SPEED;
+            continue;
+        }
+
+        stroke.0.tick(time.delta());
+
+        if *mode == MovementMode::Swimming {
+            player_accel.linear =
+                gravity.vector() * (swim.gravity_scale - 1.0) + Vec3::Y * swim.buoyancy;
+
+            if actions.player_jump && stroke.0.finished() {
+                player_vel.linear.y = swim.stroke_velocity;
+                stroke.0.reset();
             }
 
-            if on_floor {
-                break;
+            player_vel.linear.x += running.push(actions.player_x_movement, 0.5, delta);
+            player_vel.linear *= (1.0 - swim.drag * delta).max(0.0);
+            player_vel.linear.x = player_vel
+                .linear
+                .x
+                .clamp(-stats.max_speed_x, stats.max_speed_x);
+            continue;
+        }
+
+        player_accel.linear = Vec3::ZERO;
+
+        jump.since_floor = if on_floor {
+            0.0
+        } else {
+            jump.since_floor + delta
+        };
+
+        jump.buffered = if actions.player_jump_started {
+            Some(0.0)
+        } else {
+            jump.buffered
+                .map(|since_pressed| since_pressed + delta)
+                .filter(|&since_pressed| since_pressed <= jump_physics.buffer_time)
+        };
+
+        if jump.buffered.is_some() && jump.since_floor <= jump_physics.coyote_time {
+            player_vel.linear.y = stats.jump_velocity;
+            jump.buffered = None;
+            // no second jump from the same ledge while still inside the coyote time
+            jump.since_floor = f32::INFINITY;
+            jump.rising = true;
+        }
+
+        if jump.rising {
+            if player_vel.linear.y <= 0.0 {
+                jump.rising = false;
+            } else if !actions.player_jump {
+                player_vel.linear.y *= jump_physics.release_velocity_scale;
+                jump.rising = false;
+            }
+        }
+
+        // pushing into a wall only presses the player against it, and friction makes them stick
+        let input = actions
+            .player_x_movement
+            .filter(|&movement| Some(movement.signum()) != jump.wall_side);
+
+        match grounded.filter(|_| on_floor) {
+            // run along the ground, rather than into or off of a slope
+            Some(grounded) => {
+                let normal = grounded.normal.extend(0.0);
+                let along = Vec3::new(grounded.normal.y, -grounded.normal.x, 0.0);
+                let speed = player_vel.linear.dot(along);
+                player_vel.linear += along * (running.step(speed, input, 1.0, delta) - speed);
+
+                // only the part of gravity pressing into the ground, so standing on a slope
+                // doesn't slide down it
+                let gravity = gravity.vector();
+                player_accel.linear = normal * gravity.dot(normal) - gravity;
+            }
+            None => {
+                player_vel.linear.x += running.push(input, 0.5, delta);
+                player_vel.linear.x = running.settle(player_vel.linear.x, delta);
             }
         }
 
-        if on_floor && actions.player_jump {
-            player_vel.linear.y = JUMP_VELOCITY;
+        // stay on the ground when it drops away a little, e.g. walking down a slope
+        if let Some(grounded) = grounded.filter(|_| on_floor && !jump.rising) {
+            if grounded.distance > 0.0 && delta > 0.0 {
+                player_vel.linear.y = player_vel.linear.y.min(-grounded.distance / delta);
+            }
         }
+    }
+}
+
+/// Hurts the player when touching a [`level::Hazard`], knocking them away from it.
+fn touch_hazards(
+    mut damage_events: EventWriter<DamageEvent>,
+    player_query: Query<(Entity, &Transform, &Collisions, Option<&Grounded>), With<Player>>,
+    hazards_query: Query<&GlobalTransform, With<level::Hazard>>,
+) {
+    for (player, transform, collisions, grounded) in player_query.iter() {
+        // standing on a hazard hurts even while bouncing on it, between contacts
+        let touching = collisions
+            .entities()
+            .chain(grounded.map(|grounded| grounded.entity));
+
+        for hazard in touching {
+            if let Ok(hazard_transform) = hazards_query.get(hazard) {
+                let away = (transform.translation.x - hazard_transform.translation.x).signum();
 
-        // TODO: prevent player from sticking to wall by holding down direction
-        if let Some(movement) = actions.player_x_movement {
-            player_vel.linear.x += movement * MOVE_ACCEL * if on_floor { 1.0 } else { 0.5 };
+                damage_events.send(DamageEvent {
+                    target: player,
+                    source: Some(hazard),
+                    amount: 1,
+                    knockback: HAZARD_KNOCKBACK * Vec2::new(away, 1.0),
+                });
+            }
         }
+    }
+}
 
-        // damp + clamp
-        player_vel.linear.x -= 2.0 * player_vel.linear.x.signum();
-        player_vel.linear.x = player_vel.linear.x.clamp(-MAX_SPEED_X, MAX_SPEED_X);
+/// Touching a checkpoint makes it where the player comes back after dying.
+fn touch_checkpoints(
+    mut player_query: Query<(&mut Respawn, &Collisions), With<Player>>,
+    tiles_query: Query<(&level::Tile, &GlobalTransform)>,
+) {
+    for (mut respawn, collisions) in player_query.iter_mut() {
+        for entity in collisions.entities() {
+            if let Ok((level::Tile::Checkpoint, transform)) = tiles_query.get(entity) {
+                respawn.point = transform.translation.truncate().extend(PLAYER_SPAWN.z);
+            }
+        }
     }
 }
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::level::{Level, LevelGrid, LevelStreaming, LevelSystem, LoadedChunks, Tile};
+use crate::loading::MapAssets;
+use crate::GameState;
+
+pub struct PropsPlugin;
+
+/// This plugin spawns the loose physics props of a level, like crates, and the pressure
+/// plates they can hold down
+impl Plugin for PropsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PressedPlates>()
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_crates))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(freeze_crates.after(LevelSystem::Stream))
+                    .with_system(press_plates.after(LevelSystem::Stream)),
+            );
+    }
+}
+
+/// A box that can be pushed around, stacked and stood on.
+///
+/// Crates are spawned once for the whole level rather than streamed in with their chunk,
+/// so they stay wherever they were pushed. Away from the spawned chunks they are frozen in
+/// place as static bodies, or they would fall through the missing ground.
+#[derive(Component)]
+pub struct Crate;
+
+/// Pressed while something with a body rests on it, like a crate or the player.
+///
+/// Plates are streamed in with their chunk, so whether they are pressed is kept in
+/// [`PressedPlates`] instead.
+#[derive(Component)]
+pub struct PressurePlate {
+    pub cell: (usize, usize),
+}
+
+/// Cells of the pressure plates that are held down, including ones in unloaded chunks.
+#[derive(Default)]
+pub struct PressedPlates(HashSet<(usize, usize)>);
+
+impl PressedPlates {
+    pub fn is_pressed(&self, cell: (usize, usize)) -> bool {
+        self.0.contains(&cell)
+    }
+}
+
+/// Side length of a crate, relative to a tile, so it fits through gaps of one tile.
+const CRATE_SCALE: f32 = 0.8;
+
+// TODO: use actual sprites for crates and plates
+const CRATE_COLOR: Color = Color::rgb(0.55, 0.35, 0.15);
+pub(crate) const PLATE_COLOR: Color = Color::rgb(0.7, 0.1, 0.1);
+const PLATE_PRESSED_COLOR: Color = Color::rgb(0.1, 0.7, 0.1);
+
+/// Light enough for the player to push at a walk, with enough friction to stop soon after
+/// and to stay put when stacked.
+const CRATE_MATERIAL: PhysicMaterial = PhysicMaterial {
+    restitution: 0.0,
+    density: 0.4,
+    friction: 0.8,
+};
+
+fn spawn_crates(
+    mut commands: Commands,
+    maps: Res<MapAssets>,
+    level_assets: Res<Assets<Level>>,
+    grid: Res<LevelGrid>,
+) {
+    let level = level_assets.get(&maps.level0).unwrap();
+    let size = Vec2::splat(grid.tile_size * CRATE_SCALE);
+
+    for (x, y, _) in level.tiles().filter(|&(_, _, tile)| tile == Tile::Crate) {
+        // sitting on the bottom of its cell, not floating in the middle of it
+        let position = grid.cell_to_world(x, y) - Vec2::Y * (grid.tile_size - size.y) / 2.0;
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: CRATE_COLOR,
+                    custom_size: Some(size),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(20.0)),
+                ..Default::default()
+            })
+            .insert(Crate)
+            .insert(RigidBody::Dynamic)
+            .insert(CollisionShape::Cuboid {
+                half_extends: (size / 2.0).extend(0.0),
+                border_radius: None,
+            })
+            .insert(CRATE_MATERIAL)
+            // tumbling crates are hard to stack or climb
+            .insert(RotationConstraints::lock())
+            .insert(Velocity::default());
+    }
+}
+
+type CrateBody<'a> = (&'a Transform, &'a mut RigidBody, &'a mut Velocity);
+
+/// Freezes crates whose surroundings are unloaded, and lets them go again once they're back.
+fn freeze_crates(
+    maps: Res<MapAssets>,
+    level_assets: Res<Assets<Level>>,
+    grid: Res<LevelGrid>,
+    streaming: Res<LevelStreaming>,
+    loaded: Res<LoadedChunks>,
+    mut crates: Query<CrateBody, With<Crate>>,
+) {
+    let level = level_assets.get(&maps.level0).unwrap();
+
+    for (transform, mut body, mut velocity) in crates.iter_mut() {
+        let position = transform.translation.truncate();
+        let wanted = if loaded.around(&grid, &streaming, level, position) {
+            RigidBody::Dynamic
+        } else {
+            RigidBody::Static
+        };
+
+        // changing the body type recreates it, so only do it when it actually changes
+        if *body != wanted {
+            *body = wanted;
+            *velocity = Velocity::default();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn press_plates(
+    maps: Res<MapAssets>,
+    level_assets: Res<Assets<Level>>,
+    grid: Res<LevelGrid>,
+    streaming: Res<LevelStreaming>,
+    loaded: Res<LoadedChunks>,
+    mut pressed_plates: ResMut<PressedPlates>,
+    mut plates: Query<(&PressurePlate, &Transform, &Collisions, &mut Sprite)>,
+    bodies: Query<&RigidBody>,
+) {
+    let level = level_assets.get(&maps.level0).unwrap();
+
+    for (plate, transform, collisions, mut sprite) in plates.iter_mut() {
+        // crates nearby may be frozen, and frozen crates don't touch anything
+        if loaded.around(&grid, &streaming, level, transform.translation.truncate()) {
+            let pressed = collisions.entities().any(|entity| {
+                matches!(
+                    bodies.get(entity),
+                    Ok(RigidBody::Dynamic | RigidBody::KinematicPositionBased)
+                )
+            });
+
+            if pressed != pressed_plates.is_pressed(plate.cell) {
+                debug!(
+                    "pressure plate at {:?} {}",
+                    plate.cell,
+                    if pressed { "pressed" } else { "released" }
+                );
+                if pressed {
+                    pressed_plates.0.insert(plate.cell);
+                } else {
+                    pressed_plates.0.remove(&plate.cell);
+                }
+            }
+        }
+
+        // also for plates spawned this frame, which start out released
+        let color = if pressed_plates.is_pressed(plate.cell) {
+            PLATE_PRESSED_COLOR
+        } else {
+            PLATE_COLOR
+        };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::fs;
+use std::iter;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{Actions, ActionsSource, ActionsSystem};
+use crate::loading::MapAssets;
+use crate::timestep::{GameTime, FIXED_STEP};
+use crate::GameState;
+
+pub struct ReplayPlugin;
+
+/// This plugin records the `Actions` of every tick to a replay file, or plays one back in
+/// place of the keyboard and other devices, depending on the [`ReplayMode`]
+/// Either way the game runs at a fixed timestep, so the same actions make the same run
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let mode = app
+            .world
+            .get_resource::<ReplayMode>()
+            .cloned()
+            .unwrap_or_default();
+
+        match mode {
+            ReplayMode::Off => {
+                app.insert_resource(GameRng::new(rand::random()));
+            }
+            ReplayMode::Record(path) => {
+                let seed = rand::random();
+                fix_timestep(app);
+                app.insert_resource(GameRng::new(seed))
+                    .insert_resource(Recording {
+                        path,
+                        replay: Replay::new(seed),
+                    })
+                    .add_system_set(
+                        SystemSet::on_enter(GameState::Playing).with_system(start_recording),
+                    )
+                    .add_system_set(
+                        SystemSet::on_update(GameState::Playing)
+                            .with_system(record_actions.after(ActionsSystem::Read)),
+                    )
+                    .add_system_to_stage(CoreStage::Last, save_recording);
+            }
+            ReplayMode::Play(path) => match Replay::load(&path) {
+                Ok(replay) => {
+                    fix_timestep(app);
+                    app.insert_resource(GameRng::new(replay.seed))
+                        .insert_resource(ActionsSource::Injected)
+                        .insert_resource(Playback::new(replay))
+                        .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(skip_menu))
+                        .add_system_set(
+                            SystemSet::on_enter(GameState::Playing).with_system(check_level),
+                        )
+                        .add_system_set(
+                            SystemSet::on_update(GameState::Playing)
+                                .with_system(play_actions.label(ActionsSystem::Read)),
+                        );
+                }
+                Err(error) => {
+                    warn!(
+                        "Playing normally, as {} could not be replayed: {error}",
+                        path.display()
+                    );
+                    app.insert_resource(GameRng::new(rand::random()));
+                }
+            },
+        }
+    }
+}
+
+/// Whether this run is recorded, replayed, or neither.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    /// Record to a replay file, written when the game is closed.
+    Record(PathBuf),
+    /// Play a replay file back, then hand control to the player once it runs out.
+    Play(PathBuf),
+}
+
+impl ReplayMode {
+    /// Reads `--record <file>` or `--replay <file>` from the command line arguments.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        while let Some(arg) = args.next() {
+            let mode = match arg.as_str() {
+                "--record" => Self::Record,
+                "--replay" => Self::Play,
+                _ => continue,
+            };
+            if let Some(path) = args.next() {
+                return mode(path.into());
+            }
+        }
+        Self::Off
+    }
+}
+
+/// The source of randomness for anything in the game, seeded so replays draw the same numbers.
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Deref for GameRng {
+    type Target = StdRng;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rng
+    }
+}
+
+impl DerefMut for GameRng {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.rng
+    }
+}
+
+/// Everything needed to play a run again: the [`Actions`] of each of its ticks, along with
+/// how it started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    /// What the [`GameRng`] was seeded with.
+    pub seed: u64,
+    /// Asset path of the level played.
+    pub level: String,
+    /// Actions in the order they happened, each with how many ticks in a row it lasted.
+    frames: Vec<(u32, Actions)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            level: String::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Adds the actions of the next tick.
+    pub fn push(&mut self, actions: &Actions) {
+        match self.frames.last_mut() {
+            Some((count, last)) if last == actions => *count += 1,
+            _ => self.frames.push((1, actions.clone())),
+        }
+    }
+
+    /// The actions of every tick, in order.
+    pub fn into_frames(self) -> impl Iterator<Item = Actions> {
+        self.frames
+            .into_iter()
+            .flat_map(|(count, actions)| iter::repeat_n(actions, count as usize))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Makes the game advance in ticks of the same length, however long frames really take.
+fn fix_timestep(app: &mut App) {
+    app.insert_resource(GameTime::fixed(FIXED_STEP));
+}
+
+fn level_path(asset_server: &AssetServer, map_assets: &MapAssets) -> String {
+    asset_server
+        .get_handle_path(&map_assets.level0)
+        .map(|path| path.path().display().to_string())
+        .unwrap_or_default()
+}
+
+struct Recording {
+    path: PathBuf,
+    replay: Replay,
+}
+
+fn start_recording(
+    asset_server: Res<AssetServer>,
+    map_assets: Res<MapAssets>,
+    mut recording: ResMut<Recording>,
+) {
+    recording.replay.level = level_path(&asset_server, &map_assets);
+    info!("Recording to {}", recording.path.display());
+}
+
+fn record_actions(actions: Res<Actions>, mut recording: ResMut<Recording>) {
+    recording.replay.push(&actions);
+}
+
+fn save_recording(mut exits: EventReader<AppExit>, recording: Res<Recording>) {
+    if exits.iter().next().is_none() {
+        return;
+    }
+
+    match recording.replay.save(&recording.path) {
+        Ok(()) => info!("Saved the replay to {}", recording.path.display()),
+        Err(error) => warn!(
+            "Could not save the replay to {}: {error}",
+            recording.path.display()
+        ),
+    }
+}
+
+struct Playback {
+    level: String,
+    frames: Box<dyn Iterator<Item = Actions> + Send + Sync>,
+}
+
+impl Playback {
+    fn new(replay: Replay) -> Self {
+        Self {
+            level: replay.level.clone(),
+            frames: Box::new(replay.into_frames()),
+        }
+    }
+}
+
+/// The replay starts as soon as the level does, so don't wait for the play button.
+fn skip_menu(mut state: ResMut<State<GameState>>) {
+    state.set(GameState::Playing).unwrap();
+}
+
+fn check_level(
+    asset_server: Res<AssetServer>,
+    map_assets: Res<MapAssets>,
+    playback: Res<Playback>,
+) {
+    let level = level_path(&asset_server, &map_assets);
+    if playback.level != level {
+        warn!(
+            "The replay was recorded on {}, not {level}, so it will likely play out differently",
+            playback.level
+        );
+    }
+}
+
+fn play_actions(
+    mut playback: ResMut<Playback>,
+    mut actions: ResMut<Actions>,
+    mut source: ResMut<ActionsSource>,
+) {
+    if *source != ActionsSource::Injected {
+        return;
+    }
+
+    match playback.frames.next() {
+        Some(next) => *actions = next,
+        None => {
+            info!("The replay is over, handing control back to the player");
+            *actions = Actions::default();
+            *source = ActionsSource::Devices;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn moving(x: f32) -> Actions {
+        Actions {
+            player_x_movement: Some(x),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn repeated_actions_are_stored_once() {
+        let mut replay = Replay::new(0);
+        for actions in [moving(1.0), moving(1.0), moving(-1.0), moving(1.0)] {
+            replay.push(&actions);
+        }
+
+        assert_eq!(
+            replay.frames,
+            vec![(2, moving(1.0)), (1, moving(-1.0)), (1, moving(1.0))]
+        );
+    }
+
+    #[test]
+    fn saved_replay_plays_the_same_ticks() {
+        let jump = Actions {
+            player_jump: true,
+            player_jump_started: true,
+            ..moving(1.0)
+        };
+        // a stick position, which has to come back to the exact same float
+        let ticks: Vec<_> = iter::repeat_n(Actions::default(), 30)
+            .chain(iter::repeat_n(moving(1.0), 12))
+            .chain([jump])
+            .chain(iter::repeat_n(moving(0.123_456_79), 40))
+            .collect();
+
+        let mut replay = Replay::new(42);
+        replay.level = "maps/level0.lvl".to_string();
+        for actions in &ticks {
+            replay.push(actions);
+        }
+
+        let path = env::temp_dir().join("dr_fingers_replay_test.ron");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(loaded, replay);
+        assert_eq!(loaded.into_frames().collect::<Vec<_>>(), ticks);
+    }
+
+    #[test]
+    fn mode_comes_from_the_first_flag_with_a_file() {
+        let mode = |args: &[&str]| ReplayMode::from_args(args.iter().map(ToString::to_string));
+
+        assert_eq!(mode(&[]), ReplayMode::Off);
+        assert_eq!(mode(&["--record"]), ReplayMode::Off);
+        assert_eq!(
+            mode(&["--verbose", "--replay", "bug.ron"]),
+            ReplayMode::Play("bug.ron".into())
+        );
+        assert_eq!(
+            mode(&["--record", "run.ron", "--replay", "bug.ron"]),
+            ReplayMode::Record("run.ron".into())
+        );
+    }
+}
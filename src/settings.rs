@@ -0,0 +1,376 @@
+use bevy::prelude::*;
+
+use crate::actions::{BindingClash, GameControl, InputBinding, InputMap, CONTROLS_PATH};
+use crate::loading::FontAssets;
+use crate::menu::{self, ButtonColors};
+use crate::GameState;
+
+pub struct SettingsPlugin;
+
+/// This plugin draws the settings screen, where each binding of a control can be replaced or
+/// removed by clicking it, and new ones added, from the keyboard, mouse or gamepad
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>()
+            .add_system_set(SystemSet::on_enter(GameState::Settings).with_system(setup_settings))
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    // before the click that starts rebinding, so it isn't bound to that click
+                    .with_system(bind_pressed_input.before(click_settings_buttons))
+                    .with_system(click_settings_buttons)
+                    .with_system(show_bindings.after(click_settings_buttons)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Settings)
+                    .with_system(menu::despawn_screen::<SettingsScreen>)
+                    .with_system(stop_rebinding),
+            );
+    }
+}
+
+#[derive(Component)]
+struct SettingsScreen;
+
+/// Holds a row per control, respawned whenever the bindings change.
+#[derive(Component)]
+struct BindingRows;
+
+/// Says what pressing a key or button will do.
+#[derive(Component)]
+struct StatusText;
+
+#[derive(Component)]
+enum SettingsButton {
+    Binding(GameControl, InputBinding),
+    Add(GameControl),
+    Back,
+}
+
+/// What the next key or button pressed is bound to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BindTarget {
+    Add(GameControl),
+    Replace(GameControl, InputBinding),
+}
+
+#[derive(Default)]
+struct Rebinding {
+    target: Option<BindTarget>,
+    /// The last binding refused, until another one is pressed.
+    clash: Option<BindingClash>,
+}
+
+/// Cancels rebinding, and can't be bound to anything itself.
+const CANCEL_KEY: KeyCode = KeyCode::Escape;
+/// Removes the binding being replaced, and can't be bound to anything itself either.
+const REMOVE_KEY: KeyCode = KeyCode::Back;
+
+fn status(rebinding: &Rebinding) -> String {
+    let prompt = match rebinding.target {
+        None => return "Click a binding to change it, or + to add one".to_string(),
+        Some(BindTarget::Add(control)) => {
+            format!("Press a key or button for {}, or Escape", control.name())
+        }
+        Some(BindTarget::Replace(_, binding)) => {
+            format!("Press a key or button to replace {binding}, Backspace to remove it, or Escape")
+        }
+    };
+
+    match rebinding.clash {
+        Some(clash) => format!("{clash}. {prompt}"),
+        None => prompt,
+    }
+}
+
+fn setup_settings(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    input_map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+) {
+    commands
+        .spawn_bundle(menu::screen_bundle())
+        .insert(SettingsScreen)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::FlexStart,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .insert(BindingRows)
+                .with_children(|parent| {
+                    spawn_rows(parent, &font_assets, &button_colors, &input_map, &rebinding);
+                });
+
+            parent
+                .spawn_bundle(menu::button_text(&font_assets, &status(&rebinding), 20.0))
+                .insert(StatusText);
+
+            menu::spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                200.0,
+                "Back",
+                SettingsButton::Back,
+            );
+        });
+}
+
+/// A line per control: its name, a button per binding, and one to add another.
+fn spawn_rows(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    input_map: &InputMap,
+    rebinding: &Rebinding,
+) {
+    for control in GameControl::ALL {
+        parent
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                color: Color::NONE.into(),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                let mut name = menu::button_text(font_assets, control.name(), 24.0);
+                name.style.size.width = Val::Px(180.0);
+                parent.spawn_bundle(name);
+
+                for &binding in input_map.bindings(control) {
+                    let label = if rebinding.target == Some(BindTarget::Replace(control, binding)) {
+                        "...".to_string()
+                    } else {
+                        binding.to_string()
+                    };
+                    menu::spawn_small_button(
+                        parent,
+                        font_assets,
+                        button_colors,
+                        &label,
+                        SettingsButton::Binding(control, binding),
+                    );
+                }
+
+                let adding = rebinding.target == Some(BindTarget::Add(control));
+                menu::spawn_small_button(
+                    parent,
+                    font_assets,
+                    button_colors,
+                    if adding { "..." } else { "+" },
+                    SettingsButton::Add(control),
+                );
+            });
+    }
+}
+
+fn click_settings_buttons(
+    mut state: ResMut<State<GameState>>,
+    mut rebinding: ResMut<Rebinding>,
+    interaction_query: Query<(&Interaction, &SettingsButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let target = match *button {
+            SettingsButton::Binding(control, binding) => BindTarget::Replace(control, binding),
+            SettingsButton::Add(control) => BindTarget::Add(control),
+            SettingsButton::Back => {
+                state.set(GameState::Menu).unwrap();
+                continue;
+            }
+        };
+        *rebinding = Rebinding {
+            target: Some(target),
+            clash: None,
+        };
+    }
+}
+
+fn bind_pressed_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    buttons: Query<&Interaction, With<SettingsButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut input_map: ResMut<InputMap>,
+) {
+    let target = match rebinding.target {
+        Some(target) => target,
+        None => return,
+    };
+
+    if keyboard_input.just_pressed(CANCEL_KEY) {
+        *rebinding = Rebinding::default();
+        return;
+    }
+
+    if let BindTarget::Replace(control, binding) = target {
+        if keyboard_input.just_pressed(REMOVE_KEY) {
+            input_map.unbind(control, binding);
+            *rebinding = Rebinding::default();
+            save(&input_map);
+            return;
+        }
+    }
+
+    let clicked_button = buttons
+        .iter()
+        .any(|&interaction| interaction == Interaction::Clicked);
+    let pressed = pressed_binding(
+        &keyboard_input,
+        &mouse_input,
+        &gamepad_buttons,
+        clicked_button,
+    );
+    let binding = match pressed {
+        Some(binding) => binding,
+        None => return,
+    };
+
+    let result = match target {
+        BindTarget::Add(control) => input_map.bind(control, binding, None),
+        BindTarget::Replace(control, old) => input_map.bind(control, binding, Some(old)),
+    };
+    match result {
+        Ok(()) => {
+            *rebinding = Rebinding::default();
+            save(&input_map);
+        }
+        Err(clash) => rebinding.clash = Some(clash),
+    }
+}
+
+/// The first key or button pressed this frame that can be bound. A click on one of the
+/// settings buttons is left to that button, so giving up by clicking Back or another
+/// binding doesn't bind the mouse button on the way.
+fn pressed_binding(
+    keyboard_input: &Input<KeyCode>,
+    mouse_input: &Input<MouseButton>,
+    gamepad_buttons: &Input<GamepadButton>,
+    clicked_button: bool,
+) -> Option<InputBinding> {
+    keyboard_input
+        .get_just_pressed()
+        .filter(|&&key| key != CANCEL_KEY && key != REMOVE_KEY)
+        .map(|&key| InputBinding::Key(key))
+        .chain(
+            mouse_input
+                .get_just_pressed()
+                // only the left button clicks on buttons
+                .filter(|&&button| !(clicked_button && button == MouseButton::Left))
+                .map(|&button| InputBinding::Mouse(button)),
+        )
+        .chain(
+            gamepad_buttons
+                .get_just_pressed()
+                .map(|&GamepadButton(_, button)| InputBinding::Gamepad(button)),
+        )
+        .next()
+}
+
+fn save(input_map: &InputMap) {
+    if let Err(error) = input_map.save() {
+        warn!("Could not save the controls to {CONTROLS_PATH}: {error}");
+    }
+}
+
+fn show_bindings(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    input_map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+    rows_query: Query<(Entity, &Children), With<BindingRows>>,
+    mut status_query: Query<&mut Text, With<StatusText>>,
+) {
+    if !input_map.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+
+    for (rows, children) in rows_query.iter() {
+        for &child in children.iter() {
+            commands.entity(child).despawn_recursive();
+        }
+        commands.entity(rows).with_children(|parent| {
+            spawn_rows(parent, &font_assets, &button_colors, &input_map, &rebinding);
+        });
+    }
+
+    for mut text in status_query.iter_mut() {
+        text.sections[0].value = status(&rebinding);
+    }
+}
+
+fn stop_rebinding(mut rebinding: ResMut<Rebinding>) {
+    *rebinding = Rebinding::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click(button: MouseButton) -> Input<MouseButton> {
+        let mut mouse_input = Input::default();
+        mouse_input.press(button);
+        mouse_input
+    }
+
+    #[test]
+    fn clicking_a_settings_button_binds_nothing() {
+        let mut world = World::new();
+        world.insert_resource(Input::<KeyCode>::default());
+        world.insert_resource(click(MouseButton::Left));
+        world.insert_resource(Input::<GamepadButton>::default());
+        world.insert_resource(InputMap::default());
+        world.insert_resource(Rebinding {
+            target: Some(BindTarget::Replace(
+                GameControl::Jump,
+                InputBinding::Key(KeyCode::Space),
+            )),
+            clash: None,
+        });
+        world
+            .spawn()
+            .insert(SettingsButton::Back)
+            .insert(Interaction::Clicked);
+
+        SystemStage::single_threaded()
+            .with_system(bind_pressed_input)
+            .run(&mut world);
+
+        assert_eq!(*world.resource::<InputMap>(), InputMap::default());
+        assert!(world.resource::<Rebinding>().target.is_some());
+    }
+
+    #[test]
+    fn mouse_can_be_bound_away_from_the_buttons() {
+        let keys = Input::default();
+        let gamepad_buttons = Input::default();
+        let pressed = |mouse_input: &Input<MouseButton>, clicked_button| {
+            pressed_binding(&keys, mouse_input, &gamepad_buttons, clicked_button)
+        };
+
+        assert_eq!(pressed(&click(MouseButton::Left), true), None);
+        assert_eq!(
+            pressed(&click(MouseButton::Left), false),
+            Some(InputBinding::Mouse(MouseButton::Left))
+        );
+        // buttons don't react to it, so it is still meant for binding
+        assert_eq!(
+            pressed(&click(MouseButton::Right), true),
+            Some(InputBinding::Mouse(MouseButton::Right))
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::mem;
+use std::time::Duration;
+
+use bevy::core::FixedTimestep;
+use bevy::ecs::schedule::{ShouldRun, StageLabel};
+use bevy::prelude::*;
+use heron::PhysicsSteps;
+
+use crate::ground::GroundStage;
+
+pub struct TimestepPlugin;
+
+/// This plugin keeps track of how much game time passes each frame: as much as really passed,
+/// unless a fixed timestep was asked for so that runs can be reproduced
+/// Then the game and its physics run in ticks of that length instead, as many as fit in the
+/// time really passed, so it has to be added after every plugin with systems in `Update`
+impl Plugin for TimestepPlugin {
+    fn build(&self, app: &mut App) {
+        // `Time` is updated by an exclusive system, which runs before any of these
+        app.init_resource::<GameTime>()
+            .add_system_to_stage(CoreStage::First, advance_game_time);
+
+        let fixed_step = app.world.resource::<GameTime>().fixed_step;
+        if let Some(step) = fixed_step {
+            run_in_ticks(app, step);
+        }
+    }
+}
+
+/// Length of a tick with a fixed timestep.
+pub const FIXED_STEP: Duration = Duration::from_nanos(16_666_667);
+
+/// Runs the ground probes, `Update`, the physics and `PostUpdate` once per tick.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+struct TickStage;
+
+/// Time passed in the game since the last tick, or frame when the timestep isn't fixed.
+/// Gameplay reads this instead of `Time`, which can't be overridden, so a fixed timestep
+/// applies to all of it.
+#[derive(Debug, Default)]
+pub struct GameTime {
+    delta: Duration,
+    fixed_step: Option<Duration>,
+}
+
+impl GameTime {
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Game time advancing in ticks of `step`, each running the game and its physics once.
+    pub fn fixed(step: Duration) -> Self {
+        Self {
+            delta: Duration::ZERO,
+            fixed_step: Some(step),
+        }
+    }
+}
+
+fn advance_game_time(time: Res<Time>, mut game_time: ResMut<GameTime>) {
+    game_time.delta = game_time.fixed_step.unwrap_or_else(|| time.delta());
+}
+
+/// Moves the stages the game and heron run in into one that runs once for every `step` of
+/// time really passed: not at all on a frame shorter than what's left of the step, and
+/// several times in a row on a longer one. Everything else, like reading the devices and
+/// rendering, still runs once a frame.
+fn run_in_ticks(app: &mut App, step: Duration) {
+    app.insert_resource(PhysicsSteps::every_frame(step));
+    move_into_ticks(app, FixedTimestep::step(step.as_secs_f64()));
+}
+
+/// Moves every stage that has to advance together with the physics into a [`TickStage`],
+/// which runs as many times in a row as `ticks` says each frame.
+// heron deprecated naming its stage, but still creates bodies and colliders in it
+#[allow(deprecated)]
+fn move_into_ticks<Params>(app: &mut App, ticks: impl IntoSystem<(), ShouldRun, Params>) {
+    let ground = take_stage(app, GroundStage, SystemStage::single_threaded());
+    let update = take_stage(app, CoreStage::Update, SystemStage::parallel());
+    let physics = take_stage(app, heron::stage::ROOT, Schedule::default());
+    let post_update = take_stage(app, CoreStage::PostUpdate, SystemStage::parallel());
+
+    app.add_stage_after(
+        CoreStage::Update,
+        TickStage,
+        Schedule::default()
+            .with_run_criteria(ticks.system())
+            .with_stage(GroundStage, ground)
+            .with_stage(CoreStage::Update, update)
+            .with_stage(heron::stage::ROOT, physics)
+            .with_stage(CoreStage::PostUpdate, post_update),
+    );
+}
+
+/// Swaps the stage labelled `label` with an empty `stage`, keeping its place in the schedule.
+fn take_stage<S: Stage>(app: &mut App, label: impl StageLabel, mut stage: S) -> S {
+    app.stage(label, |taken: &mut S| {
+        mem::swap(taken, &mut stage);
+        taken
+    });
+    stage
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::core::CorePlugin;
+    use bevy::transform::TransformPlugin;
+    use heron::prelude::*;
+
+    use super::*;
+    use crate::ground::{GroundPlugin, GroundProbe, Grounded};
+
+    /// How many ticks the next frame runs.
+    struct TicksPerFrame(u32);
+
+    fn run_ticks(mut left: Local<Option<u32>>, per_frame: Res<TicksPerFrame>) -> ShouldRun {
+        let left = left.get_or_insert(per_frame.0);
+        if *left == 0 {
+            *left = per_frame.0;
+            return ShouldRun::No;
+        }
+        *left -= 1;
+        ShouldRun::YesAndCheckAgain
+    }
+
+    /// Whether the body was grounded on each tick, and the ticks it jumped on.
+    #[derive(Default)]
+    struct Ticks {
+        grounded: Vec<bool>,
+        jumps: Vec<usize>,
+    }
+
+    /// Stands in for the player's movement: jumps whenever it is on the ground, which only
+    /// matches up if the ground is probed again before every tick.
+    fn jump_when_grounded(
+        mut ticks: ResMut<Ticks>,
+        mut query: Query<(&mut Velocity, Option<&Grounded>)>,
+    ) {
+        for (mut velocity, grounded) in query.iter_mut() {
+            let tick = ticks.grounded.len();
+            ticks.grounded.push(grounded.is_some());
+            if grounded.is_some() && velocity.linear.y <= 0.0 {
+                velocity.linear.y = 60.0;
+                ticks.jumps.push(tick);
+            }
+        }
+    }
+
+    fn run(ticks_per_frame: u32, ticks: u32) -> Ticks {
+        let mut app = App::new();
+        app.add_plugin(CorePlugin)
+            .add_plugin(TransformPlugin)
+            .add_plugin(PhysicsPlugin::default())
+            .add_plugin(GroundPlugin)
+            .insert_resource(Gravity::from(Vec2::new(0.0, -250.0)))
+            .insert_resource(PhysicsSteps::every_frame(FIXED_STEP))
+            .insert_resource(TicksPerFrame(ticks_per_frame))
+            .init_resource::<Ticks>()
+            .add_system(jump_when_grounded);
+        move_into_ticks(&mut app, run_ticks);
+
+        app.world
+            .spawn()
+            .insert_bundle(TransformBundle::default())
+            .insert(RigidBody::Static)
+            .insert(CollisionShape::Cuboid {
+                half_extends: Vec3::new(100.0, 8.0, 0.0),
+                border_radius: None,
+            });
+        app.world
+            .spawn()
+            .insert_bundle(TransformBundle::from_transform(Transform::from_xyz(
+                0.0, 30.0, 0.0,
+            )))
+            .insert(RigidBody::Dynamic)
+            .insert(CollisionShape::Cuboid {
+                half_extends: Vec3::new(6.0, 6.0, 0.0),
+                border_radius: None,
+            })
+            .insert(RotationConstraints::lock())
+            .insert(Velocity::default())
+            .insert(GroundProbe::default());
+
+        for _ in 0..ticks / ticks_per_frame {
+            app.update();
+        }
+        app.world.remove_resource::<Ticks>().unwrap()
+    }
+
+    #[test]
+    fn ground_is_the_same_however_many_ticks_a_frame_runs() {
+        let one = run(1, 120);
+        let two = run(2, 120);
+
+        assert!(!one.jumps.is_empty(), "it should land and jump");
+        assert_eq!(one.grounded, two.grounded);
+        assert_eq!(one.jumps, two.jumps);
+    }
+}
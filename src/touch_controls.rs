@@ -0,0 +1,308 @@
+use std::collections::HashSet;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use crate::actions::{ActionsSystem, GameControl, TouchedControls};
+use crate::loading::FontAssets;
+use crate::GameState;
+
+pub struct TouchControlsPlugin;
+
+/// This plugin shows on-screen buttons once the screen is touched, e.g. on a phone playing the
+/// web build, and feeds whatever is held down on them into the `Actions`
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .init_resource::<TouchPoints>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                gather_touches.label(TouchSystem::Gather).after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                read_touch_controls
+                    .after(TouchSystem::Gather)
+                    .before(ActionsSystem::Gather),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(show_touch_controls),
+            );
+
+        #[cfg(target_arch = "wasm32")]
+        app.add_startup_system(web::listen_for_touches.exclusive_system());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+enum TouchSystem {
+    Gather,
+}
+
+/// Whether the on-screen controls may be shown.
+pub struct TouchControls {
+    /// Once disabled, they stay hidden even while touching the screen.
+    pub enabled: bool,
+}
+
+impl Default for TouchControls {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Component)]
+struct TouchOverlay;
+
+/// Where the screen is touched this frame, in the same coordinates as the UI: logical pixels
+/// from the bottom left corner of the window.
+#[derive(Debug, Default)]
+struct TouchPoints {
+    held: Vec<Vec2>,
+    /// Whether a new touch started this frame.
+    started: bool,
+}
+
+/// A button of the overlay, held down by any touch inside it.
+#[derive(Component)]
+struct TouchButton(GameControl);
+
+const BUTTON_SIZE: f32 = 80.0;
+const BUTTON_MARGIN: f32 = 16.0;
+const BUTTON_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.2);
+const BUTTON_HELD_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.45);
+
+/// Arrows in the bottom left corner, and actions in the bottom right, as (column, row) from
+/// the nearest corner in button sizes.
+const ARROWS: [(GameControl, &str, f32, f32); 4] = [
+    (GameControl::Left, "<", 0.0, 0.5),
+    (GameControl::Right, ">", 2.0, 0.5),
+    (GameControl::Up, "^", 1.0, 1.0),
+    (GameControl::Down, "v", 1.0, 0.0),
+];
+const ACTIONS: [(GameControl, &str, f32, f32); 2] = [
+    (GameControl::Jump, "Jump", 0.0, 0.0),
+    (GameControl::Dash, "Dash", 1.0, 0.5),
+];
+
+/// Touches come from `Touches`, except on the web, where winit doesn't pass them on.
+#[cfg(not(target_arch = "wasm32"))]
+fn gather_touches(touches: Res<Touches>, windows: Res<Windows>, mut points: ResMut<TouchPoints>) {
+    let height = windows.get_primary().map_or(0.0, |window| window.height());
+
+    points.held = touches
+        .iter()
+        .map(|touch| {
+            let position = touch.position();
+            // bevy_winit only flips touches to start from the bottom on phones
+            if cfg!(any(target_os = "android", target_os = "ios")) {
+                position
+            } else {
+                Vec2::new(position.x, height - position.y)
+            }
+        })
+        .collect();
+    points.started = touches.iter_just_pressed().next().is_some();
+}
+
+#[cfg(target_arch = "wasm32")]
+fn gather_touches(web_touches: NonSend<web::WebTouches>, mut points: ResMut<TouchPoints>) {
+    let mut touches = web_touches.0.borrow_mut();
+
+    points.held = touches.held.clone();
+    points.started = std::mem::take(&mut touches.started);
+}
+
+fn show_touch_controls(
+    mut commands: Commands,
+    touches: Res<TouchPoints>,
+    settings: Res<TouchControls>,
+    font_assets: Res<FontAssets>,
+    overlay: Query<Entity, With<TouchOverlay>>,
+) {
+    if !settings.enabled {
+        for entity in overlay.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    if !overlay.is_empty() || !touches.started {
+        return;
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(TouchOverlay)
+        .with_children(|parent| {
+            for (control, label, column, row) in ARROWS {
+                let position = Rect {
+                    left: Val::Px(BUTTON_MARGIN + column * (BUTTON_SIZE + BUTTON_MARGIN)),
+                    bottom: Val::Px(BUTTON_MARGIN + row * (BUTTON_SIZE + BUTTON_MARGIN)),
+                    ..Default::default()
+                };
+                spawn_touch_button(parent, &font_assets, control, label, position);
+            }
+
+            for (control, label, column, row) in ACTIONS {
+                let position = Rect {
+                    right: Val::Px(BUTTON_MARGIN + column * (BUTTON_SIZE + BUTTON_MARGIN)),
+                    bottom: Val::Px(BUTTON_MARGIN + row * (BUTTON_SIZE + BUTTON_MARGIN)),
+                    ..Default::default()
+                };
+                spawn_touch_button(parent, &font_assets, control, label, position);
+            }
+        });
+}
+
+fn spawn_touch_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    control: GameControl,
+    label: &str,
+    position: Rect<Val>,
+) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(BUTTON_SIZE), Val::Px(BUTTON_SIZE)),
+                position_type: PositionType::Absolute,
+                position,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: BUTTON_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(TouchButton(control))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 30.0,
+                        color: Color::rgba(1.0, 1.0, 1.0, 0.8),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+/// Checks every touch against every button, so several can be held at once, unlike with
+/// `Interaction` which only follows the first touch.
+fn read_touch_controls(
+    touches: Res<TouchPoints>,
+    mut touched: ResMut<TouchedControls>,
+    mut buttons: Query<(&TouchButton, &Node, &GlobalTransform, &mut UiColor)>,
+) {
+    let mut held = HashSet::new();
+
+    for (button, node, transform, mut color) in buttons.iter_mut() {
+        let center = transform.translation.truncate();
+        let is_held = touches.held.iter().any(|&touch| {
+            let offset = (touch - center).abs();
+            offset.x <= node.size.x / 2.0 && offset.y <= node.size.y / 2.0
+        });
+
+        if is_held {
+            held.insert(button.0);
+        }
+        *color = if is_held {
+            BUTTON_HELD_COLOR
+        } else {
+            BUTTON_COLOR
+        }
+        .into();
+    }
+
+    touched.update(held);
+}
+
+/// Touch listeners on the canvas, as winit's web backend never sends touch events.
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use bevy::prelude::*;
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+    use web_sys::{HtmlCanvasElement, TouchEvent};
+
+    /// Touches on the canvas, kept up to date by the listeners between frames.
+    #[derive(Default)]
+    pub(super) struct WebTouches(pub(super) Rc<RefCell<WebTouchState>>);
+
+    #[derive(Default)]
+    pub(super) struct WebTouchState {
+        pub(super) held: Vec<Vec2>,
+        /// Whether a touch started since the last frame.
+        pub(super) started: bool,
+    }
+
+    const TOUCH_EVENTS: [&str; 4] = ["touchstart", "touchmove", "touchend", "touchcancel"];
+
+    pub(super) fn listen_for_touches(world: &mut World) {
+        let touches = WebTouches::default();
+
+        let canvas = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.query_selector("canvas").ok().flatten())
+            .and_then(|element| element.dyn_into::<HtmlCanvasElement>().ok());
+
+        match canvas {
+            Some(canvas) => {
+                for event in TOUCH_EVENTS {
+                    listen(&canvas, event, touches.0.clone());
+                }
+            }
+            None => warn!("There is no canvas to listen for touches on"),
+        }
+
+        world.insert_non_send_resource(touches);
+    }
+
+    fn listen(canvas: &HtmlCanvasElement, event: &str, state: Rc<RefCell<WebTouchState>>) {
+        let target = canvas.clone();
+        let listener = Closure::wrap(Box::new(move |event: TouchEvent| {
+            // keep the browser from scrolling or zooming the page instead
+            event.prevent_default();
+
+            let rect = target.get_bounding_client_rect();
+            let list = event.touches();
+            let mut state = state.borrow_mut();
+
+            // the list has every touch still on the screen, not just the ones that changed
+            state.held = (0..list.length())
+                .filter_map(|i| list.get(i))
+                .map(|touch| {
+                    Vec2::new(
+                        (f64::from(touch.client_x()) - rect.left()) as f32,
+                        (rect.bottom() - f64::from(touch.client_y())) as f32,
+                    )
+                })
+                .collect();
+            state.started |= event.type_() == "touchstart";
+        }) as Box<dyn FnMut(TouchEvent)>);
+
+        if let Err(error) =
+            canvas.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+        {
+            warn!("Could not listen for {event} on the canvas: {error:?}");
+        }
+        // the listener lives as long as the page
+        listener.forget();
+    }
+}